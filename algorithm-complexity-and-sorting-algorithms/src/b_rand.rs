@@ -1,15 +1,86 @@
-use std::sync::Mutex;
+use std::cell::RefCell;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-lazy_static::lazy_static! {
-    static ref RG: Mutex<RandGen> = Mutex::new(RandGen::new(34052));
+// Each thread gets its own generator, so the parallel sorts
+// never wait on each other for a pivot like they did on a global Mutex.
+// Every thread gets a different seed, but which one depends on how many
+// threads drew before it, and the threaded sorts start new ones each call,
+// so pivots are not repeatable. The _with_rng sorts take a seeded generator for that
+static NEXT_SEED: AtomicUsize = AtomicUsize::new(34052);
+
+thread_local! {
+    static LOCAL: RefCell<RandGen> =
+        RefCell::new(RandGen::new(NEXT_SEED.fetch_add(7919, Ordering::Relaxed)));
 }
 
 pub fn rand(max: usize) -> usize {
-    // note: unwrap is bad
-    // if others hold the mutex and panics this can fail
-    RG.lock().unwrap().next_v(max)
+    LOCAL.with(|r| r.borrow_mut().next_v(max))
+}
+
+// Where the quick sorts get their pivot from
+pub trait PivotRng {
+    // a value in 0..max with every value equally likely
+    fn next_below(&mut self, max: usize) -> usize;
+
+    // index of the pivot to use for v, v is never empty.
+    // a random index by default, is_less lets an
+    // implementation look at the values instead
+    fn pivot<T, F>(&mut self, v: &[T], _is_less: &mut F) -> usize
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        self.next_below(v.len())
+    }
+}
+
+// A source of random bits, and the helpers every generator gets from them
+pub trait Random {
+    fn next_u64(&mut self) -> u64;
+
+    // a value in 0..max with every value equally likely, panics if max is 0
+    fn below(&mut self, max: u64) -> u64 {
+        below(1 << 64, max as u128, || self.next_u64() as u128) as u64
+    }
+
+    // a value in r, panics if r is empty
+    fn range(&mut self, r: Range<i64>) -> i64 {
+        assert!(r.start < r.end, "empty range {:?}", r);
+        let width = r.end.wrapping_sub(r.start) as u64;
+        r.start.wrapping_add(self.below(width) as i64)
+    }
+
+    // a float in 0..1, the top 53 bits as that is all an f64 holds
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Fisher Yates, every order equally likely
+    fn shuffle<T>(&mut self, v: &mut [T]) {
+        for i in (1..v.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+            v.swap(i, j);
+        }
+    }
 }
 
+// Draws from 0..range until one lands below the biggest multiple of max.
+// Plain % max would favour the low values whenever max does not divide range
+fn below<F: FnMut() -> u128>(range: u128, max: u128, mut draw: F) -> u128 {
+    assert!(max > 0, "below 0, there is nothing in 0..0 to pick");
+    let limit = range - range % max;
+    loop {
+        let x = draw();
+        if x < limit {
+            return x % max;
+        }
+    }
+}
+
+// Linear congruential generator.
+// The modulus is 29 * 397 * 2019851 and the multiplier doesn't suit it,
+// so the period is only 199965150, not the modulus. See rand_stats
+#[derive(Debug, Clone, PartialEq)]
 pub struct RandGen {
     curr: usize,
     mul: usize,
@@ -19,17 +90,228 @@ pub struct RandGen {
 
 impl RandGen {
     pub fn new(curr: usize) -> Self {
+        let modulo = 23254544563;
         RandGen {
-            curr,
+            // keep curr * mul from overflowing
+            curr: curr % modulo,
             mul: 56394237,
             inc: 34642349,
-            modulo: 23254544563,
+            modulo,
         }
     }
 
-    pub fn next_v(&mut self, max: usize) -> usize {
+    // an LCG of your own, curr * mul must fit in a usize
+    pub fn with_params(curr: usize, mul: usize, inc: usize, modulo: usize) -> Self {
+        RandGen {
+            curr: curr % modulo,
+            mul,
+            inc,
+            modulo,
+        }
+    }
+
+    // the raw next value, in 0..modulo
+    pub fn step(&mut self) -> usize {
         self.curr = (self.curr * self.mul + self.inc) % self.modulo;
-        self.curr % max
+        self.curr
+    }
+
+    pub fn next_v(&mut self, max: usize) -> usize {
+        let m = self.modulo as u128;
+        if max as u128 <= m {
+            return below(m, max as u128, || self.step() as u128) as usize;
+        }
+        // wider than one step, join two steps into one number
+        below(m * m, max as u128, || {
+            let hi = self.step() as u128;
+            hi * m + self.step() as u128
+        }) as usize
+    }
+}
+
+impl PivotRng for RandGen {
+    fn next_below(&mut self, max: usize) -> usize {
+        self.next_v(max)
+    }
+}
+
+impl Random for RandGen {
+    // two steps give more than 64 bits, throw away the top
+    // of the range that doesn't fill a whole 2^64
+    fn next_u64(&mut self) -> u64 {
+        let m = self.modulo as u128;
+        below(m * m, 1 << 64, || {
+            let hi = self.step() as u128;
+            hi * m + self.step() as u128
+        }) as u64
+    }
+}
+
+// Marsaglia's xorshift, fast with a full 64 bit output.
+// The low bits are weak, XorShiftStar fixes that for one multiply
+#[derive(Debug, Clone, PartialEq)]
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        // zero is the one state xorshift never leaves
+        XorShift {
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+}
+
+impl PivotRng for XorShift {
+    fn next_below(&mut self, max: usize) -> usize {
+        below(1 << 64, max as u128, || self.next_u64() as u128) as usize
+    }
+}
+
+impl Random for XorShift {
+    fn next_u64(&mut self) -> u64 {
+        XorShift::next_u64(self)
+    }
+}
+
+// xorshift64*, xorshift with the output multiplied by a constant
+// so every bit depends on every state bit. Period 2^64 - 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct XorShiftStar {
+    state: u64,
+}
+
+impl XorShiftStar {
+    pub fn new(seed: u64) -> Self {
+        XorShiftStar {
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+}
+
+impl Random for XorShiftStar {
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+impl PivotRng for XorShiftStar {
+    fn next_below(&mut self, max: usize) -> usize {
+        self.below(max as u64) as usize
+    }
+}
+
+// O'Neill's PCG32, an LCG mod 2^64 with its high bits shuffled
+// by a rotate the state picks. Period 2^64, and each
+// stream (any odd increment) is a different sequence
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+impl Pcg32 {
+    const MUL: u64 = 6364136223846793005;
+
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut p = Pcg32 {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+        p.next_u32();
+        p.state = p.state.wrapping_add(seed);
+        p.next_u32();
+        p
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(Pcg32::MUL).wrapping_add(self.inc);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+}
+
+impl Random for Pcg32 {
+    fn next_u64(&mut self) -> u64 {
+        let hi = self.next_u32() as u64;
+        (hi << 32) | self.next_u32() as u64
+    }
+}
+
+impl PivotRng for Pcg32 {
+    fn next_below(&mut self, max: usize) -> usize {
+        self.below(max as u64) as usize
+    }
+}
+
+// No randomness at all, the median of the values a quarter,
+// half and three quarters of the way along.
+// Not first, middle and last, as pivot moves the smallest value
+// to the end of the left side, which would then be picked every time.
+// Repeatable, and sorted or reversed input get a perfect pivot
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MedianOfThree;
+
+impl PivotRng for MedianOfThree {
+    fn next_below(&mut self, max: usize) -> usize {
+        max / 2
+    }
+
+    fn pivot<T, F>(&mut self, v: &[T], is_less: &mut F) -> usize
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        if v.len() < 3 {
+            return 0;
+        }
+        let n = v.len();
+        let (mut a, mut b, mut c) = (n / 4, n / 2, n * 3 / 4);
+        // order the three indices by value, b ends up the median
+        if is_less(&v[b], &v[a]) {
+            std::mem::swap(&mut a, &mut b);
+        }
+        if is_less(&v[c], &v[b]) {
+            std::mem::swap(&mut b, &mut c);
+            if is_less(&v[b], &v[a]) {
+                std::mem::swap(&mut a, &mut b);
+            }
+        }
+        b
+    }
+}
+
+// The default, uses the generator local to the calling thread.
+// Free to copy, so every rayon worker just uses its own
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadRng;
+
+impl PivotRng for ThreadRng {
+    fn next_below(&mut self, max: usize) -> usize {
+        rand(max)
     }
 }
 
@@ -44,4 +326,99 @@ mod tests {
             println!("--{}", r.next_v(100));
         }
     }
+    #[test]
+    fn test_next_below_in_range() {
+        // cargo test test_next_below_in_range -- --nocapture
+        let mut r = RandGen::new(12);
+        let mut x = XorShift::new(12);
+        for max in 1..200 {
+            for _ in 0..50 {
+                assert!(r.next_below(max) < max);
+                assert!(x.next_below(max) < max);
+                assert!(rand(max) < max);
+            }
+        }
+        // wider than the LCG modulus
+        let big = usize::MAX / 3;
+        assert!(r.next_below(big) < big);
+    }
+    #[test]
+    fn test_below_unbiased() {
+        // cargo test test_below_unbiased -- --nocapture
+        // a range of 10 into 0..4, % would give 0 and 1 three times each
+        let mut n = 0;
+        let mut counts = [0; 4];
+        for _ in 0..10_000 {
+            let x = below(10, 4, || {
+                n = (n + 1) % 10;
+                n
+            });
+            counts[x as usize] += 1;
+        }
+        assert_eq!(counts, [2_500; 4]);
+    }
+    #[test]
+    fn test_median_of_three() {
+        // cargo test test_median_of_three -- --nocapture
+        let mut less = |a: &i32, b: &i32| a < b;
+        let mut m = MedianOfThree;
+        for v in &[
+            [1, 2, 3],
+            [1, 3, 2],
+            [2, 1, 3],
+            [2, 3, 1],
+            [3, 1, 2],
+            [3, 2, 1],
+        ] {
+            let p = m.pivot(v, &mut less);
+            assert_eq!(v[p], 2);
+        }
+        assert_eq!(m.pivot(&[5, 1], &mut less), 0);
+    }
+    #[test]
+    fn test_seeded_repeatable() {
+        // cargo test test_seeded_repeatable -- --nocapture
+        let mut a = XorShift::new(7);
+        let mut b = XorShift::new(7);
+        for _ in 0..100 {
+            assert_eq!(a.next_below(1000), b.next_below(1000));
+        }
+    }
+    #[test]
+    fn test_random_helpers() {
+        // cargo test test_random_helpers -- --nocapture
+        let mut r = Pcg32::new(42, 54);
+        for _ in 0..1_000 {
+            let x = r.range(-5..5);
+            assert!((-5..5).contains(&x));
+            let f = r.next_f64();
+            assert!((0.0..1.0).contains(&f));
+            assert!(r.below(3) < 3);
+        }
+        // the whole of i64 is one range
+        r.range(i64::MIN..i64::MAX);
+
+        let mut v: Vec<u32> = (0..100).collect();
+        XorShiftStar::new(3).shuffle(&mut v);
+        assert_ne!(v, (0..100).collect::<Vec<u32>>());
+        v.sort();
+        assert_eq!(v, (0..100).collect::<Vec<u32>>());
+    }
+    #[test]
+    #[should_panic(expected = "nothing in 0..0")]
+    fn test_below_zero() {
+        // cargo test test_below_zero -- --nocapture
+        XorShift::new(1).below(0);
+    }
+    #[test]
+    fn test_pcg32_reference() {
+        // cargo test test_pcg32_reference -- --nocapture
+        // first outputs of pcg32-demo from the PCG paper's C code
+        let mut r = Pcg32::new(42, 54);
+        let first: Vec<u32> = (0..6).map(|_| r.next_u32()).collect();
+        assert_eq!(
+            first,
+            vec![0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e]
+        );
+    }
 }
@@ -0,0 +1,175 @@
+// Runs every sort over generated inputs and writes one CSV row per run,
+// so the Big-O claims in the comments can be checked against real curves.
+//
+// cargo run --release --bin sort-bench -- --sizes 1000,10000,100000 --seeds 1,2,3 --out bench.csv
+//
+// columns: sort,input,size,seed,micros,comparisons,swaps
+// comparisons and swaps are empty for sorts that don't report to an observer.
+// micros is from a run with no observer, the counts from a second run
+
+use algorithm_complexity_and_sorting_algorithms::*;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::time::Instant;
+
+// sorts that are O(n^2) on average get very slow past this, so bigger sizes skip them
+const QUADRATIC_LIMIT: usize = 20_000;
+
+// The 2 way quick sorts put every key equal to the pivot on one side,
+// so few_unique input recurses about n / 8 deep. quick does that on the
+// bench thread, which has the stack for it, but threaded and rayon do it
+// on worker threads with the default stack, so past this they skip it
+const PARALLEL_TWO_WAY: [&str; 2] = ["threaded", "rayon"];
+const FEW_UNIQUE_LIMIT: usize = 20_000;
+
+// the bench itself runs on one thread with room for that recursion
+const STACK_SIZE: usize = 1 << 28;
+
+const INPUTS: [&str; 6] = [
+    "random",
+    "sorted",
+    "reversed",
+    "nearly_sorted",
+    "few_unique",
+    "sawtooth",
+];
+
+fn input(kind: &str, n: usize, seed: u64) -> Vec<u32> {
+    let mut r = XorShift::new(seed);
+    match kind {
+        // a permutation of 0..n, every order equally likely
+        "random" => {
+            let mut v: Vec<u32> = (0..n as u32).collect();
+            r.shuffle(&mut v);
+            v
+        }
+        "sorted" => (0..n as u32).collect(),
+        "reversed" => (0..n as u32).rev().collect(),
+        // sorted with 1 in 100 swapped somewhere else
+        "nearly_sorted" => {
+            let mut v: Vec<u32> = (0..n as u32).collect();
+            for _ in 0..n / 100 {
+                let (a, b) = (r.below(n as u64), r.below(n as u64));
+                v.swap(a as usize, b as usize);
+            }
+            v
+        }
+        "few_unique" => (0..n).map(|_| r.below(8) as u32).collect(),
+        // ascending runs of about sqrt(n)
+        "sawtooth" => {
+            let tooth = ((n as f64).sqrt() as usize).max(1);
+            (0..n).map(|i| (i % tooth) as u32).collect()
+        }
+        _ => unreachable!(),
+    }
+}
+
+struct Args {
+    sizes: Vec<usize>,
+    seeds: Vec<u64>,
+    out: Option<String>,
+}
+
+fn parse_list<T: std::str::FromStr>(flag: &str, s: Option<String>) -> Vec<T> {
+    let s = s.unwrap_or_else(|| usage(&format!("{} needs a value", flag)));
+    s.split(',')
+        .map(|x| {
+            x.trim()
+                .parse()
+                .unwrap_or_else(|_| usage(&format!("bad value {:?} for {}", x, flag)))
+        })
+        .collect()
+}
+
+fn usage(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    eprintln!("usage: sort-bench [--sizes 1000,10000] [--seeds 1,2,3] [--out file.csv]");
+    std::process::exit(2);
+}
+
+fn parse_args() -> Args {
+    let mut a = Args {
+        sizes: vec![1_000, 10_000, 100_000],
+        seeds: vec![1],
+        out: None,
+    };
+    let mut it = std::env::args().skip(1);
+    while let Some(flag) = it.next() {
+        match flag.as_str() {
+            "--sizes" => a.sizes = parse_list(&flag, it.next()),
+            "--seeds" => a.seeds = parse_list(&flag, it.next()),
+            "--out" => a.out = Some(it.next().unwrap_or_else(|| usage("--out needs a value"))),
+            "-h" | "--help" => usage("sort-bench, times every sort over generated inputs"),
+            _ => usage(&format!("unknown argument {}", flag)),
+        }
+    }
+    a
+}
+
+fn main() -> io::Result<()> {
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(bench)?
+        .join()
+        .expect("bench panicked")
+}
+
+fn bench() -> io::Result<()> {
+    let args = parse_args();
+    let mut out: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout())),
+    };
+
+    writeln!(out, "sort,input,size,seed,micros,comparisons,swaps")?;
+    // every sort in the crate, the registry says which ones count
+    for sort in int_sorters::<u32>() {
+        let info = sort.info();
+        for &n in &args.sizes {
+            if info.average == Complexity::N2 && n > QUADRATIC_LIMIT {
+                eprintln!("skipping {} at {}, over {}", info.name, n, QUADRATIC_LIMIT);
+                continue;
+            }
+            for kind in INPUTS.iter() {
+                if *kind == "few_unique"
+                    && PARALLEL_TWO_WAY.contains(&info.name)
+                    && n > FEW_UNIQUE_LIMIT
+                {
+                    eprintln!(
+                        "skipping {} on few_unique {}, over {}",
+                        info.name, n, FEW_UNIQUE_LIMIT
+                    );
+                    continue;
+                }
+                for &seed in &args.seeds {
+                    let mut v = input(kind, n, seed);
+                    // timed with no observer, counting costs an atomic per event
+                    let start = Instant::now();
+                    sort.sort(&mut v);
+                    let micros = start.elapsed().as_micros();
+
+                    assert!(
+                        v.windows(2).all(|w| w[0] <= w[1]),
+                        "{} left {} input unsorted",
+                        info.name,
+                        kind
+                    );
+                    if info.observed {
+                        // then counted on a second run, the seed gives the same input
+                        let c = Counter::new();
+                        sort.sort_observed(&mut input(kind, n, seed), &c);
+                        let t = c.totals();
+                        writeln!(
+                            out,
+                            "{},{},{},{},{},{},{}",
+                            info.name, kind, n, seed, micros, t.comparisons, t.swaps
+                        )?;
+                    } else {
+                        writeln!(out, "{},{},{},{},{},,", info.name, kind, n, seed, micros)?;
+                    }
+                }
+            }
+        }
+    }
+    out.flush()
+}
@@ -0,0 +1,331 @@
+// Animates a sort in the terminal with the array drawn as vertical bars.
+// The sort runs first with a Recorder watching, then its events are
+// played back on a copy of the input one at a time:
+// yellow bars are being compared, red ones swapped, and a green
+// bar is a pivot that has reached its final place.
+//
+// cargo run --bin sort-viz -- --sort quick --size 40 --delay 50
+//
+// keys: + faster, - slower, space pause, n one step while paused, q quit
+//
+// Only swaps are replayed, so merge and tim sort, which move items
+// through a buffer, can't be shown. --list gives the ones that can
+// and names the ones that can't
+
+use algorithm_complexity_and_sorting_algorithms::*;
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::{clear, color, cursor};
+
+const INPUTS: [&str; 5] = ["random", "sorted", "reversed", "few_unique", "sawtooth"];
+
+// status lines under the bars
+const STATUS_ROWS: u16 = 3;
+
+const MAX_DELAY: u64 = 2_000;
+
+// values 1..=n so the tallest bar is always the full height
+fn input(kind: &str, n: usize, seed: u64) -> Vec<u32> {
+    let mut r = XorShift::new(seed);
+    let n32 = n as u32;
+    match kind {
+        "random" => {
+            let mut v: Vec<u32> = (1..=n32).collect();
+            r.shuffle(&mut v);
+            v
+        }
+        "sorted" => (1..=n32).collect(),
+        "reversed" => (1..=n32).rev().collect(),
+        "few_unique" => (0..n).map(|_| (r.below(4) + 1) as u32 * n32 / 4).collect(),
+        "sawtooth" => {
+            let tooth = ((n as f64).sqrt() as u32).max(1);
+            (0..n32).map(|i| (i % tooth + 1) * n32 / tooth).collect()
+        }
+        _ => unreachable!(),
+    }
+}
+
+// sorts whose swaps are all there is to replay
+fn can_show(info: &SortInfo) -> bool {
+    info.in_place && info.observed
+}
+
+// the rest, named so no one has to find out by trying
+fn cant_show() -> String {
+    sorters::<u32>()
+        .iter()
+        .map(|s| s.info())
+        .filter(|info| !can_show(info))
+        .map(|info| info.name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+struct Args {
+    sort: String,
+    input: String,
+    size: Option<usize>,
+    delay: u64,
+    seed: u64,
+}
+
+fn usage(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    eprintln!(
+        "usage: sort-viz [--sort quick] [--input {}] [--size 40] [--delay 40] [--seed 1] [--list]",
+        INPUTS.join("|")
+    );
+    eprintln!(
+        "only swaps are replayed, these move items another way and can't be shown: {}",
+        cant_show()
+    );
+    std::process::exit(2);
+}
+
+fn parse<T: std::str::FromStr>(flag: &str, s: Option<String>) -> T {
+    let s = s.unwrap_or_else(|| usage(&format!("{} needs a value", flag)));
+    s.parse()
+        .unwrap_or_else(|_| usage(&format!("bad value {:?} for {}", s, flag)))
+}
+
+fn list() -> ! {
+    for s in sorters::<u32>() {
+        let info = s.info();
+        if can_show(&info) {
+            println!("{:12} {}", info.name, info.average);
+        }
+    }
+    println!(
+        "\ncan't be shown, they don't sort by swapping: {}",
+        cant_show()
+    );
+    std::process::exit(0);
+}
+
+fn parse_args() -> Args {
+    let mut a = Args {
+        sort: "quick".to_string(),
+        input: "random".to_string(),
+        size: None,
+        delay: 40,
+        seed: 1,
+    };
+    let mut it = std::env::args().skip(1);
+    while let Some(flag) = it.next() {
+        match flag.as_str() {
+            "--sort" => a.sort = parse(&flag, it.next()),
+            "--input" => a.input = parse(&flag, it.next()),
+            "--size" => a.size = Some(parse(&flag, it.next())),
+            "--delay" => a.delay = parse(&flag, it.next()),
+            "--seed" => a.seed = parse(&flag, it.next()),
+            "--list" => list(),
+            "-h" | "--help" => usage("sort-viz, watch a sort step by step"),
+            _ => usage(&format!("unknown argument {}", flag)),
+        }
+    }
+    if !INPUTS.contains(&a.input.as_str()) {
+        usage(&format!("unknown input {}", a.input));
+    }
+    a
+}
+
+// the array part way through the replay and what to colour
+struct Viz {
+    name: &'static str,
+    v: Vec<u32>,
+    events: Vec<Event>,
+    step: usize,
+    comparisons: usize,
+    swaps: usize,
+    // the bars in the current event
+    compare: Option<(usize, usize)>,
+    swap: Option<(usize, usize)>,
+    // pivots known to be in their final place
+    placed: Vec<bool>,
+}
+
+impl Viz {
+    fn new(name: &'static str, v: Vec<u32>, events: Vec<Event>) -> Self {
+        let placed = vec![false; v.len()];
+        Viz {
+            name,
+            v,
+            events,
+            step: 0,
+            comparisons: 0,
+            swaps: 0,
+            compare: None,
+            swap: None,
+            placed,
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.step >= self.events.len()
+    }
+
+    // plays events up to and including the next one worth a frame,
+    // depth and pass don't change the picture
+    fn next(&mut self) {
+        self.compare = None;
+        self.swap = None;
+        while let Some(&e) = self.events.get(self.step) {
+            self.step += 1;
+            match e {
+                Event::Compare(a, b) => {
+                    self.comparisons += 1;
+                    self.compare = Some((a, b));
+                    return;
+                }
+                Event::Swap(a, b) => {
+                    self.swaps += 1;
+                    self.v.swap(a, b);
+                    self.swap = Some((a, b));
+                    return;
+                }
+                Event::Partition { p, .. } => {
+                    self.placed[p] = true;
+                    return;
+                }
+                Event::Depth(_) | Event::Pass => {}
+            }
+        }
+    }
+
+    fn colour(&self, i: usize) -> String {
+        let is = |pair: Option<(usize, usize)>| pair.is_some_and(|(a, b)| a == i || b == i);
+        if self.done() || self.placed[i] {
+            color::Fg(color::Green).to_string()
+        } else if is(self.swap) {
+            color::Fg(color::Red).to_string()
+        } else if is(self.compare) {
+            color::Fg(color::Yellow).to_string()
+        } else {
+            color::Fg(color::White).to_string()
+        }
+    }
+
+    // the whole frame goes out in one write so it doesn't flicker
+    fn draw<W: Write>(&self, t: &mut W, delay: u64, paused: bool) -> io::Result<()> {
+        let (w, h) = termion::terminal_size()?;
+        let rows = h.saturating_sub(STATUS_ROWS).max(1) as u32;
+        let width = (w as usize / self.v.len().max(1)).max(1);
+        let max = self.v.iter().copied().max().unwrap_or(1).max(1);
+
+        let mut s = format!("{}{}", clear::All, cursor::Goto(1, 1));
+        for row in 0..rows {
+            // bars grow up from the bottom row
+            let level = rows - row;
+            for (i, &x) in self.v.iter().enumerate() {
+                let tall = (x as u64 * rows as u64).div_ceil(max as u64) as u32;
+                if tall >= level {
+                    s += &self.colour(i);
+                    s += &"█".repeat(width.saturating_sub(1).max(1));
+                    if width > 1 {
+                        s += " ";
+                    }
+                } else {
+                    s += &" ".repeat(width);
+                }
+            }
+            s += "\r\n";
+        }
+        s += &format!("{}", color::Fg(color::Reset));
+        s += &format!(
+            "{}  step {}/{}  comparisons:{} swaps:{}  delay {}ms{}\r\n",
+            self.name,
+            self.step,
+            self.events.len(),
+            self.comparisons,
+            self.swaps,
+            delay,
+            if paused { "  paused" } else { "" },
+        );
+        if self.done() {
+            s += "sorted, q to quit";
+        } else {
+            s += "+ faster  - slower  space pause  n step  q quit";
+        }
+        write!(t, "{}", s)?;
+        t.flush()
+    }
+}
+
+// get keyboard input in a thread
+fn keys() -> Receiver<Key> {
+    let (send, read) = mpsc::channel();
+    std::thread::spawn(move || {
+        for key in io::stdin().keys().flatten() {
+            if send.send(key).is_err() {
+                return;
+            }
+        }
+    });
+    read
+}
+
+fn run(viz: &mut Viz, mut delay: u64) -> io::Result<()> {
+    let keys = keys();
+    let mut screen = io::stdout().into_raw_mode()?;
+    write!(screen, "{}", cursor::Hide)?;
+    let mut paused = false;
+    loop {
+        let mut step = !paused && !viz.done();
+        while let Ok(key) = keys.try_recv() {
+            match key {
+                Key::Char('q') | Key::Esc | Key::Ctrl('c') => {
+                    write!(screen, "{}{}", clear::All, cursor::Goto(1, 1))?;
+                    write!(screen, "{}", cursor::Show)?;
+                    return screen.flush();
+                }
+                Key::Char('+') | Key::Char('=') => delay /= 2,
+                Key::Char('-') => delay = (delay * 2).clamp(1, MAX_DELAY),
+                Key::Char(' ') => paused = !paused,
+                Key::Char('n') => step = !viz.done(),
+                _ => {}
+            }
+        }
+        if step {
+            viz.next();
+        }
+        viz.draw(&mut screen, delay, paused)?;
+        // nothing left to show, just wait for q
+        let wait = if viz.done() || paused { 50 } else { delay };
+        std::thread::sleep(Duration::from_millis(wait));
+    }
+}
+
+fn main() -> io::Result<()> {
+    let args = parse_args();
+    let sort = sorter::<u32>(&args.sort)
+        .unwrap_or_else(|| usage(&format!("unknown sort {}, --list shows them", args.sort)));
+    let info = sort.info();
+    if !can_show(&info) {
+        usage(&format!(
+            "{} moves items without swapping them, --list shows the sorts that can be played back",
+            info.name
+        ));
+    }
+
+    // enough bars to fill the terminal two columns each
+    let size = args.size.unwrap_or_else(|| {
+        let (w, _) = termion::terminal_size().unwrap_or((80, 24));
+        (w as usize / 2).clamp(2, 64)
+    });
+    let v = input(&args.input, size, args.seed);
+
+    let rec = Recorder::new();
+    let mut sorted_v = v.clone();
+    sort.sort_observed(&mut sorted_v, &rec);
+
+    let mut viz = Viz::new(info.name, v, rec.into_events());
+    run(&mut viz, args.delay)?;
+    if viz.done() {
+        assert_eq!(viz.v, sorted_v, "the replay should end where the sort did");
+    }
+    Ok(())
+}
@@ -0,0 +1,332 @@
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::heap::BinaryHeap;
+use crate::timsort::tim_sort_by;
+
+pub const DEFAULT_MEMORY: usize = 64 << 20;
+
+// buffer for each run being merged, so a merge pass
+// reads at most memory / RUN_BUF runs at once
+const RUN_BUF: usize = 64 << 10;
+
+#[derive(Debug)]
+pub enum ExternalError {
+    IO(io::Error),
+    Bincode(bincode::Error),
+}
+
+impl fmt::Display for ExternalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExternalError::IO(e) => write!(f, "IO {}", e),
+            ExternalError::Bincode(e) => write!(f, "BinCode {}", e),
+        }
+    }
+}
+
+impl Error for ExternalError {}
+
+impl From<io::Error> for ExternalError {
+    fn from(e: io::Error) -> Self {
+        ExternalError::IO(e)
+    }
+}
+
+impl From<bincode::Error> for ExternalError {
+    fn from(e: bincode::Error) -> Self {
+        ExternalError::Bincode(e)
+    }
+}
+
+// what a sort did, mostly to see how much went to disk
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ExternalStats {
+    pub records: usize,
+    // sorted chunks, 1 means it all fit and nothing was spilled
+    pub runs: usize,
+    // times the data went through a k-way merge
+    pub merge_passes: usize,
+}
+
+// External merge sort for more records than fit in memory.
+// Reads bincode records from r until it runs out,
+// sorts chunks of about memory bytes with tim sort and spills each
+// to a temp file, then k-way merges the files into w as bincode again.
+// Each merge opens at most memory / 64K files, more runs than that
+// take extra passes. Stable, temp files go in TMPDIR and are
+// deleted when done
+pub fn external_sort<T, R, W>(r: R, w: W, memory: usize) -> Result<ExternalStats, ExternalError>
+where
+    T: Serialize + DeserializeOwned + PartialOrd,
+    R: Read,
+    W: Write,
+{
+    external_sort_by(r, w, memory, |a: &T, b: &T| {
+        a.partial_cmp(b).unwrap_or(Ordering::Equal)
+    })
+}
+
+pub fn external_sort_by_key<T, K, R, W, F>(
+    r: R,
+    w: W,
+    memory: usize,
+    mut f: F,
+) -> Result<ExternalStats, ExternalError>
+where
+    T: Serialize + DeserializeOwned,
+    K: PartialOrd,
+    R: Read,
+    W: Write,
+    F: FnMut(&T) -> K,
+{
+    external_sort_by(r, w, memory, |a: &T, b: &T| {
+        f(a).partial_cmp(&f(b)).unwrap_or(Ordering::Equal)
+    })
+}
+
+pub fn external_sort_by<T, R, W, F>(
+    r: R,
+    w: W,
+    memory: usize,
+    mut compare: F,
+) -> Result<ExternalStats, ExternalError>
+where
+    T: Serialize + DeserializeOwned,
+    R: Read,
+    W: Write,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut r = BufReader::new(r);
+    let mut w = BufWriter::new(w);
+    let mut stats = ExternalStats::default();
+
+    let mut runs = Vec::new();
+    loop {
+        let mut chunk = read_chunk(&mut r, memory)?;
+        if chunk.is_empty() {
+            break;
+        }
+        stats.records += chunk.len();
+        tim_sort_by(&mut chunk, &mut compare);
+
+        // it all fit, no need for the disk
+        if runs.is_empty() && r.fill_buf()?.is_empty() {
+            stats.runs = 1;
+            write_all(&mut w, &chunk)?;
+            w.flush()?;
+            return Ok(stats);
+        }
+        let mut f = BufWriter::new(tempfile::tempfile()?);
+        write_all(&mut f, &chunk)?;
+        runs.push(rewind(f)?);
+    }
+    stats.runs = runs.len();
+
+    let fan_in = (memory / RUN_BUF).max(2);
+    while runs.len() > fan_in {
+        // merging neighbouring runs keeps earlier records first, so still stable
+        let mut next = Vec::new();
+        let mut it = runs.into_iter().peekable();
+        while it.peek().is_some() {
+            let group: Vec<File> = it.by_ref().take(fan_in).collect();
+            let mut f = BufWriter::new(tempfile::tempfile()?);
+            merge_files::<T, _, _>(group, &mut f, &mut compare)?;
+            next.push(rewind(f)?);
+        }
+        runs = next;
+        stats.merge_passes += 1;
+    }
+    if !runs.is_empty() {
+        merge_files::<T, _, _>(runs, &mut w, &mut compare)?;
+        stats.merge_passes += 1;
+    }
+    w.flush()?;
+    Ok(stats)
+}
+
+// the next record, None at the end of the input.
+// running out part way through a record is an error
+fn read_record<T, R>(r: &mut R) -> Result<Option<T>, ExternalError>
+where
+    T: DeserializeOwned,
+    R: BufRead,
+{
+    if r.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(bincode::deserialize_from(r)?))
+}
+
+// records until their rough size passes memory, always at least one
+fn read_chunk<T, R>(r: &mut R, memory: usize) -> Result<Vec<T>, ExternalError>
+where
+    T: Serialize + DeserializeOwned,
+    R: BufRead,
+{
+    let mut chunk = Vec::new();
+    let mut used = 0;
+    while used < memory {
+        match read_record::<T, _>(r)? {
+            Some(t) => {
+                // the encoded size stands in for anything t owns on the heap
+                used += std::mem::size_of::<T>() + bincode::serialized_size(&t)? as usize;
+                chunk.push(t);
+            }
+            None => break,
+        }
+    }
+    Ok(chunk)
+}
+
+fn write_all<T: Serialize, W: Write>(w: &mut W, v: &[T]) -> Result<(), ExternalError> {
+    for t in v {
+        bincode::serialize_into(&mut *w, t)?;
+    }
+    Ok(())
+}
+
+// finish writing a run and get it ready to read back
+fn rewind(f: BufWriter<File>) -> Result<File, ExternalError> {
+    let mut f = f.into_inner().map_err(|e| e.into_error())?;
+    f.seek(SeekFrom::Start(0))?;
+    Ok(f)
+}
+
+// k-way merge, a min heap holds the next record from each run
+// ties go to the earlier run
+fn merge_files<T, W, F>(runs: Vec<File>, w: &mut W, compare: &mut F) -> Result<(), ExternalError>
+where
+    T: Serialize + DeserializeOwned,
+    W: Write,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut readers: Vec<BufReader<File>> = runs
+        .into_iter()
+        .map(|f| BufReader::with_capacity(RUN_BUF, f))
+        .collect();
+    let mut heap =
+        BinaryHeap::with_comparator(|a: &(T, usize), b: &(T, usize)| match compare(&a.0, &b.0) {
+            Ordering::Equal => b.1.cmp(&a.1),
+            o => o.reverse(),
+        });
+    for (i, r) in readers.iter_mut().enumerate() {
+        if let Some(t) = read_record(r)? {
+            heap.push((t, i));
+        }
+    }
+    while let Some((t, i)) = heap.pop() {
+        bincode::serialize_into(&mut *w, &t)?;
+        if let Some(t) = read_record(&mut readers[i])? {
+            heap.push((t, i));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::b_rand::XorShift;
+    use serde_derive::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    struct LogLine {
+        time: u32,
+        msg: String,
+    }
+
+    fn encode<T: Serialize>(v: &[T]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_all(&mut buf, v).unwrap();
+        buf
+    }
+
+    fn decode<T: DeserializeOwned>(mut buf: &[u8]) -> Vec<T> {
+        let mut res = Vec::new();
+        while let Some(t) = read_record(&mut buf).unwrap() {
+            res.push(t);
+        }
+        res
+    }
+
+    #[test]
+    fn test_external_sort() {
+        // cargo test test_external_sort -- --nocapture
+        let mut r = XorShift::new(8);
+        let v: Vec<u64> = (0..20_000).map(|_| r.next_u64() % 5_000).collect();
+        let mut sorted_v = v.clone();
+        sorted_v.sort();
+
+        // 4K per chunk, so lots of runs and merges of 2 at a time
+        let mut out = Vec::new();
+        let stats = external_sort::<u64, _, _>(&encode(&v)[..], &mut out, 4_096).unwrap();
+        println!("{:?}", stats);
+        assert_eq!(decode::<u64>(&out), sorted_v);
+        assert_eq!(stats.records, 20_000);
+        assert!(stats.runs > 10);
+        assert!(stats.merge_passes > 1);
+
+        // a bigger budget merges them all in one go
+        let mut out = Vec::new();
+        let stats = external_sort::<u64, _, _>(&encode(&v)[..], &mut out, 64 << 20).unwrap();
+        assert_eq!(decode::<u64>(&out), sorted_v);
+        assert_eq!(stats.runs, 1);
+        assert_eq!(stats.merge_passes, 0);
+
+        let mut out = Vec::new();
+        let stats = external_sort::<u64, _, _>(&encode(&v)[..], &mut out, 200_000).unwrap();
+        assert_eq!(decode::<u64>(&out), sorted_v);
+        assert!(stats.runs > 1);
+        assert_eq!(stats.merge_passes, 1);
+    }
+    #[test]
+    fn test_external_sort_by_key_stable() {
+        // cargo test test_external_sort_by_key_stable -- --nocapture
+        let v: Vec<LogLine> = (0..3_000)
+            .map(|i| LogLine {
+                time: (i * 7_919) % 101,
+                msg: format!("event {}", i),
+            })
+            .collect();
+        let mut sorted_v = v.clone();
+        sorted_v.sort_by_key(|l| l.time);
+
+        let mut out = Vec::new();
+        let stats =
+            external_sort_by_key(&encode(&v)[..], &mut out, 2_000, |l: &LogLine| l.time).unwrap();
+        assert!(stats.runs > 1);
+        assert_eq!(decode::<LogLine>(&out), sorted_v);
+
+        let mut out = Vec::new();
+        external_sort_by(&encode(&v)[..], &mut out, 2_000, |a: &LogLine, b| {
+            b.time.cmp(&a.time)
+        })
+        .unwrap();
+        let res = decode::<LogLine>(&out);
+        assert!(res.windows(2).all(|w| w[0].time >= w[1].time));
+    }
+    #[test]
+    fn test_external_sort_empty_and_broken() {
+        // cargo test test_external_sort_empty_and_broken -- --nocapture
+        let mut out = Vec::new();
+        let stats = external_sort::<u32, _, _>(&[][..], &mut out, 1_000).unwrap();
+        assert_eq!(stats, ExternalStats::default());
+        assert!(out.is_empty());
+
+        // half a record at the end
+        let mut buf = encode(&[3u32, 1, 2]);
+        buf.pop();
+        let res = external_sort::<u32, _, _>(&buf[..], &mut out, 1_000);
+        match res {
+            Err(ExternalError::Bincode(_)) => {}
+            r => panic!("expected a bincode error, got {:?}", r),
+        }
+    }
+}
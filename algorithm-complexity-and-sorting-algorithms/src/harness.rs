@@ -0,0 +1,369 @@
+// Property tests for every sort in the registry.
+// Thousands of inputs from a seeded RandGen, so a failure
+// always comes back with the same case number and input.
+// Checks each output is a sorted permutation equal to slice::sort,
+// that stable sorts keep equal keys in input order and that
+// the parallel sorts agree with the sequential ones
+use std::cmp::Ordering;
+
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+use crate::b_rand::{RandGen, Random};
+use crate::merge::merge_sort_rayon_impl;
+use crate::radix::{counting_sort_by_key, radix_sort_by_key};
+use crate::sorter::{int_sorters, sorter, sorters, Complexity, Sorter};
+use crate::{threaded_quick_sort_at, NoOp, ThreadBudget};
+
+const CASES: usize = 2_000;
+const MAX_LEN: usize = 10_000;
+const STACK_SIZE: usize = 1 << 28;
+
+// THREAD_CUTOFF and MERGE_RAYON_THRESHOLD are about as long as the
+// longest input, so the registry's parallel sorts hardly ever split here.
+// The parallel path is tested again with this cutoff
+const PAR_CUTOFF: usize = 16;
+// threads in the pool, and the most the threaded sort may start
+const THREADS: usize = 4;
+
+// O(n^2) sorts only see inputs up to this long
+const QUADRATIC_MAX: usize = 600;
+
+// counting sort only sees inputs with keys this close together
+const COUNTING_RANGE: i64 = 1 << 16;
+
+const SHAPES: [&str; 10] = [
+    "random",
+    "permutation",
+    "few_unique",
+    "extremes",
+    "sorted",
+    "reversed",
+    "all_equal",
+    "nearly_sorted",
+    "organ_pipe",
+    "sawtooth",
+];
+
+// Compares by key only, the tag says where it started
+#[derive(Debug, Clone, Copy)]
+struct Tagged {
+    key: i32,
+    tag: usize,
+}
+
+impl PartialEq for Tagged {
+    fn eq(&self, o: &Self) -> bool {
+        self.key == o.key
+    }
+}
+
+impl PartialOrd for Tagged {
+    fn partial_cmp(&self, o: &Self) -> Option<Ordering> {
+        self.key.partial_cmp(&o.key)
+    }
+}
+
+// mostly short inputs with the odd long one, lengths near 0 are
+// where the off by one bugs live
+fn gen_len(r: &mut RandGen) -> usize {
+    let n = match r.below(100) {
+        0..=59 => r.below(20),
+        60..=94 => r.below(300),
+        95..=98 => r.below(2_000),
+        _ => r.below(MAX_LEN as u64 + 1),
+    };
+    n as usize
+}
+
+fn gen_key(r: &mut RandGen) -> i32 {
+    r.range(i32::MIN as i64..i32::MAX as i64 + 1) as i32
+}
+
+fn gen_case(r: &mut RandGen, shape: &str, n: usize) -> Vec<i32> {
+    let mut v: Vec<i32> = match shape {
+        "random" => (0..n).map(|_| gen_key(r)).collect(),
+        // every key different
+        "permutation" => {
+            let mut v: Vec<i32> = (0..n as i32).collect();
+            r.shuffle(&mut v);
+            v
+        }
+        "few_unique" => {
+            let k = r.range(1..9);
+            (0..n).map(|_| r.range(-3..k - 3) as i32).collect()
+        }
+        "extremes" => (0..n)
+            .map(|_| match r.below(5) {
+                0 => i32::MIN,
+                1 => i32::MAX,
+                2 => 0,
+                3 => -1,
+                _ => gen_key(r),
+            })
+            .collect(),
+        "sorted" | "reversed" | "nearly_sorted" => {
+            let mut v: Vec<i32> = (0..n).map(|_| r.below(n as u64 * 2 + 1) as i32).collect();
+            v.sort();
+            v
+        }
+        "all_equal" => vec![gen_key(r); n],
+        "organ_pipe" => (0..n).map(|i| i.min(n - i) as i32).collect(),
+        "sawtooth" => {
+            let tooth = r.range(1..31) as usize;
+            (0..n).map(|i| (i % tooth) as i32).collect()
+        }
+        _ => unreachable!(),
+    };
+    if shape == "reversed" {
+        v.reverse();
+    }
+    if shape == "nearly_sorted" && n > 1 {
+        for _ in 0..r.range(1..6) {
+            let (a, b) = (r.below(n as u64), r.below(n as u64));
+            v.swap(a as usize, b as usize);
+        }
+    }
+    v
+}
+
+fn tagged(v: &[i32]) -> Vec<Tagged> {
+    v.iter()
+        .enumerate()
+        .map(|(tag, &key)| Tagged { key, tag })
+        .collect()
+}
+
+fn pairs(v: &[Tagged]) -> Vec<(i32, usize)> {
+    v.iter().map(|t| (t.key, t.tag)).collect()
+}
+
+fn key_range(v: &[i32]) -> i64 {
+    match (v.iter().min(), v.iter().max()) {
+        (Some(&lo), Some(&hi)) => hi as i64 - lo as i64,
+        _ => 0,
+    }
+}
+
+fn skip<T>(s: &dyn Sorter<T>, v: &[i32]) -> bool {
+    let info = s.info();
+    (info.average == Complexity::N2 && v.len() > QUADRATIC_MAX)
+        || (info.worst == Complexity::NPlusK && key_range(v) > COUNTING_RANGE)
+}
+
+// runs f on every case, the case number and shape go in any failure
+fn for_each_case<F: FnMut(&str, &[i32])>(seed: usize, mut f: F) {
+    let mut r = RandGen::new(seed);
+    for case in 0..CASES {
+        let shape = SHAPES[case % SHAPES.len()];
+        let n = gen_len(&mut r);
+        let v = gen_case(&mut r, shape, n);
+        f(&format!("case {} {} len {}", case, shape, n), &v);
+    }
+}
+
+// The 2 way quick sorts go about n calls deep on all equal input,
+// too deep for the stack a test gets. Like sort-bench, run on
+// threads with a big stack, a pool of them so the rayon sorts have one too.
+// A set number of them, so work is shared out even on one core
+fn big_stack<F: FnOnce() + Send>(f: F) {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(THREADS)
+        .stack_size(STACK_SIZE)
+        .build()
+        .unwrap()
+        .install(f);
+}
+
+// the start of an input for a failure message, short ones
+// come out whole and can be pasted into a test
+fn small(v: &[i32]) -> &[i32] {
+    &v[..v.len().min(64)]
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prop_sorted_permutation() {
+        // cargo test test_prop_sorted_permutation -- --nocapture
+        big_stack(|| {
+            let all = int_sorters::<i32>();
+            for_each_case(1, |case, v| {
+                let mut want = v.to_vec();
+                want.sort();
+                for s in &all {
+                    if skip(s.as_ref(), v) {
+                        continue;
+                    }
+                    let mut got = v.to_vec();
+                    s.sort(&mut got);
+                    if got != want {
+                        panic!("{} failed {}, input {:?}", s.info().name, case, small(v));
+                    }
+                }
+            });
+        });
+    }
+    #[test]
+    fn test_prop_permutation_of_tags() {
+        // cargo test test_prop_permutation_of_tags -- --nocapture
+        big_stack(|| {
+            // nothing lost or copied, every tag comes out exactly once
+            let all = sorters::<Tagged>();
+            for_each_case(2, |case, v| {
+                for s in &all {
+                    if skip(s.as_ref(), v) {
+                        continue;
+                    }
+                    let mut got = tagged(v);
+                    s.sort(&mut got);
+                    let keys_sorted = got.windows(2).all(|w| w[0].key <= w[1].key);
+                    let mut tags: Vec<usize> = got.iter().map(|t| t.tag).collect();
+                    tags.sort();
+                    let every_tag = tags.iter().enumerate().all(|(i, t)| i == *t);
+                    let keys_kept = got.iter().all(|t| t.key == v[t.tag]);
+                    if !(keys_sorted && every_tag && keys_kept) {
+                        panic!("{} failed {}, input {:?}", s.info().name, case, small(v));
+                    }
+                }
+            });
+        });
+    }
+    #[test]
+    fn test_prop_stable() {
+        // cargo test test_prop_stable -- --nocapture
+        big_stack(|| {
+            let stable: Vec<_> = sorters::<Tagged>()
+                .into_iter()
+                .filter(|s| s.info().stable)
+                .collect();
+            for_each_case(3, |case, v| {
+                let mut want = tagged(v);
+                want.sort_by_key(|t| t.key);
+                let want = pairs(&want);
+                for s in &stable {
+                    if skip(s.as_ref(), v) {
+                        continue;
+                    }
+                    let mut got = tagged(v);
+                    s.sort(&mut got);
+                    if pairs(&got) != want {
+                        panic!(
+                            "{} not stable {}, input {:?}",
+                            s.info().name,
+                            case,
+                            small(v)
+                        );
+                    }
+                }
+
+                // the integer sorts are stable through their by_key versions
+                let mut got = tagged(v);
+                radix_sort_by_key(&mut got, |t| t.key);
+                assert_eq!(pairs(&got), want, "radix not stable {}", case);
+                if key_range(v) <= COUNTING_RANGE {
+                    let mut got = tagged(v);
+                    counting_sort_by_key(&mut got, |t| t.key);
+                    assert_eq!(pairs(&got), want, "counting not stable {}", case);
+                }
+            });
+        });
+    }
+    #[test]
+    fn test_prop_parallel_agrees() {
+        // cargo test test_prop_parallel_agrees -- --nocapture
+        big_stack(|| {
+            // (parallel, the sequential sort it splits up)
+            let twins = [
+                ("merge_rayon", "merge"),
+                ("threaded", "quick"),
+                ("rayon", "quick"),
+                ("rayon_3way", "quick_3way"),
+            ];
+            let twins: Vec<_> = twins
+                .iter()
+                .map(|(p, s)| (sorter::<Tagged>(p).unwrap(), sorter::<Tagged>(s).unwrap()))
+                .collect();
+            for_each_case(4, |case, v| {
+                for (par, seq) in &twins {
+                    let mut a = tagged(v);
+                    let mut b = tagged(v);
+                    par.sort(&mut a);
+                    seq.sort(&mut b);
+                    let (name, stable) = (par.info().name, par.info().stable);
+                    // unstable sorts pick random pivots, so only the keys have to match
+                    let same = if stable {
+                        pairs(&a) == pairs(&b)
+                    } else {
+                        a.iter().map(|t| t.key).eq(b.iter().map(|t| t.key))
+                    };
+                    if !same {
+                        panic!("{} disagrees {}, input {:?}", name, case, small(v));
+                    }
+                }
+            });
+        });
+    }
+    #[test]
+    fn test_prop_parallel_paths() {
+        // cargo test test_prop_parallel_paths -- --nocapture
+        big_stack(|| {
+            let (mut spawned, mut spread) = (0, 0);
+            for_each_case(5, |case, v| {
+                let mut want = tagged(v);
+                want.sort_by_key(|t| t.key);
+
+                // threaded quick sort with a small cutoff, the budget counts its threads
+                let budget = ThreadBudget::new(THREADS, PAR_CUTOFF);
+                let mut got = tagged(v);
+                threaded_quick_sort_at(
+                    &mut got,
+                    0,
+                    0,
+                    &budget,
+                    &mut |a: &Tagged, b: &Tagged| a < b,
+                    &NoOp,
+                );
+                if !got.iter().map(|t| t.key).eq(want.iter().map(|t| t.key)) {
+                    panic!("threaded disagrees {}, input {:?}", case, small(v));
+                }
+                let started = THREADS - budget.left.load(AtomicOrdering::SeqCst);
+                if started > 0 {
+                    spawned += 1;
+                }
+                // with this many different keys some partition on the
+                // left is past the cutoff, unless every pivot is in the lowest 16
+                let mut keys = v.to_vec();
+                keys.sort();
+                keys.dedup();
+                assert!(
+                    keys.len() < 256 || started > 0,
+                    "threaded never split {}",
+                    case
+                );
+
+                // merge_rayon, each comparison notes the pool thread it ran on
+                let seen = AtomicUsize::new(0);
+                let mut got = tagged(v);
+                merge_sort_rayon_impl(&mut got, PAR_CUTOFF, &|a: &Tagged, b: &Tagged| {
+                    let t = rayon::current_thread_index().unwrap();
+                    seen.fetch_or(1 << t, AtomicOrdering::Relaxed);
+                    a < b
+                });
+                if pairs(&got) != pairs(&want) {
+                    panic!("merge_rayon disagrees {}, input {:?}", case, small(v));
+                }
+                if seen.into_inner().count_ones() > 1 {
+                    spread += 1;
+                }
+            });
+            println!(
+                "threaded started threads in {} cases, merge_rayon ran on more than one in {}",
+                spawned, spread
+            );
+            // a third of the cases are 20 long or more
+            assert!(spawned > CASES / 10, "{}", spawned);
+            assert!(spread > 0);
+        });
+    }
+}
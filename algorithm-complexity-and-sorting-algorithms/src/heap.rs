@@ -0,0 +1,340 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::FromIterator;
+
+use crate::observer::{NoOp, SortObserver};
+
+// Heap sort, O(n ln(n)) always, in place, not stable
+pub fn heap_sort<T: PartialOrd>(v: &mut [T]) {
+    heap_sort_observed(v, &NoOp);
+}
+
+pub fn heap_sort_observed<T: PartialOrd, O: SortObserver<T>>(v: &mut [T], obs: &O) {
+    heap_sort_at(v, 0, &mut |a: &T, b: &T| a < b, obs);
+}
+
+pub fn heap_sort_by<T, F>(v: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    heap_sort_at(
+        v,
+        0,
+        &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    );
+}
+
+pub fn heap_sort_by_key<T, K, F>(v: &mut [T], mut f: F)
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    heap_sort_at(v, 0, &mut |a: &T, b: &T| f(a) < f(b), &NoOp);
+}
+
+// O(n ln(n)) always, not stable
+pub(crate) fn heap_sort_at<T, F, O>(v: &mut [T], off: usize, is_less: &mut F, obs: &O)
+where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    // build a max heap, leaves are already heaps
+    for i in (0..v.len() / 2).rev() {
+        sift_down_at(v, i, v.len(), off, is_less, obs);
+    }
+    // move the biggest to the end and shrink the heap
+    for end in (1..v.len()).rev() {
+        obs.swap(off, off + end);
+        v.swap(0, end);
+        sift_down_at(v, 0, end, off, is_less, obs);
+    }
+}
+
+pub(crate) fn sift_down_at<T, F, O>(
+    v: &mut [T],
+    mut root: usize,
+    end: usize,
+    off: usize,
+    is_less: &mut F,
+    obs: &O,
+) where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    loop {
+        let mut child = 2 * root + 1;
+        if child >= end {
+            return;
+        }
+        // pick the bigger child
+        if child + 1 < end {
+            obs.compare(off + child, off + child + 1);
+            if is_less(&v[child], &v[child + 1]) {
+                child += 1;
+            }
+        }
+        obs.compare(off + root, off + child);
+        if !is_less(&v[root], &v[child]) {
+            return;
+        }
+        obs.swap(off + root, off + child);
+        v.swap(root, child);
+        root = child;
+    }
+}
+
+// move v[i] up until its parent is not less than it
+fn sift_up<T, F>(v: &mut [T], mut i: usize, is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    while i > 0 {
+        let parent = (i - 1) / 2;
+        if !is_less(&v[parent], &v[i]) {
+            return;
+        }
+        v.swap(parent, i);
+        i = parent;
+    }
+}
+
+// PartialOrd as a comparator, values that do not compare count as equal
+fn max_first<T: PartialOrd>(a: &T, b: &T) -> Ordering {
+    a.partial_cmp(b).unwrap_or(Ordering::Equal)
+}
+
+fn min_first<T: PartialOrd>(a: &T, b: &T) -> Ordering {
+    b.partial_cmp(a).unwrap_or(Ordering::Equal)
+}
+
+// Binary heap on a Vec, usable as a priority queue
+// the top is the greatest item by compare, so a reversed
+// compare gives a min heap.
+// Stored as a tree where the children of i are 2i+1 and 2i+2
+pub struct BinaryHeap<T, F = fn(&T, &T) -> Ordering> {
+    data: Vec<T>,
+    compare: F,
+}
+
+impl<T: PartialOrd> BinaryHeap<T> {
+    // biggest first
+    pub fn new() -> Self {
+        BinaryHeap::with_comparator(max_first as fn(&T, &T) -> Ordering)
+    }
+
+    // smallest first
+    pub fn new_min() -> Self {
+        BinaryHeap::with_comparator(min_first as fn(&T, &T) -> Ordering)
+    }
+
+    pub fn from_vec(v: Vec<T>) -> Self {
+        BinaryHeap::from_vec_by(v, max_first as fn(&T, &T) -> Ordering)
+    }
+
+    pub fn from_vec_min(v: Vec<T>) -> Self {
+        BinaryHeap::from_vec_by(v, min_first as fn(&T, &T) -> Ordering)
+    }
+}
+
+impl<T: PartialOrd> Default for BinaryHeap<T> {
+    fn default() -> Self {
+        BinaryHeap::new()
+    }
+}
+
+impl<T, F> BinaryHeap<T, F>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    pub fn with_comparator(compare: F) -> Self {
+        BinaryHeap {
+            data: Vec::new(),
+            compare,
+        }
+    }
+
+    // heapify in place, O(n) rather than n pushes at O(n ln(n))
+    pub fn from_vec_by(data: Vec<T>, compare: F) -> Self {
+        let mut h = BinaryHeap { data, compare };
+        let compare = &mut h.compare;
+        let mut is_less = |a: &T, b: &T| compare(a, b) == Ordering::Less;
+        let len = h.data.len();
+        for i in (0..len / 2).rev() {
+            sift_down_at(&mut h.data, i, len, 0, &mut is_less, &NoOp);
+        }
+        h
+    }
+
+    pub fn push(&mut self, t: T) {
+        self.data.push(t);
+        let compare = &mut self.compare;
+        let i = self.data.len() - 1;
+        sift_up(&mut self.data, i, &mut |a: &T, b: &T| {
+            compare(a, b) == Ordering::Less
+        });
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        // put the last item on top then let it sink
+        let res = self.data.pop().map(|mut last| {
+            if !self.data.is_empty() {
+                std::mem::swap(&mut last, &mut self.data[0]);
+            }
+            last
+        });
+        let compare = &mut self.compare;
+        let len = self.data.len();
+        sift_down_at(
+            &mut self.data,
+            0,
+            len,
+            0,
+            &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+            &NoOp,
+        );
+        res
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    // the items in heap order
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
+    // the items smallest first by compare, the same as std
+    // this is the second half of heap sort
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        let compare = &mut self.compare;
+        let mut is_less = |a: &T, b: &T| compare(a, b) == Ordering::Less;
+        for end in (1..self.data.len()).rev() {
+            self.data.swap(0, end);
+            sift_down_at(&mut self.data, 0, end, 0, &mut is_less, &NoOp);
+        }
+        self.data
+    }
+}
+
+impl<T: fmt::Debug, F> fmt::Debug for BinaryHeap<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BinaryHeap")
+            .field("data", &self.data)
+            .finish()
+    }
+}
+
+impl<T: PartialOrd> FromIterator<T> for BinaryHeap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        BinaryHeap::from_vec(iter.into_iter().collect())
+    }
+}
+
+impl<T, F> Extend<T> for BinaryHeap<T, F>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for t in iter {
+            self.push(t);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::b_rand::RandGen;
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap as StdHeap;
+
+    #[test]
+    fn test_heap_sort() {
+        // cargo test test_heap_sort -- --nocapture
+        let mut v = vec![4, 6, 1, 19, 8, 11, 13, 3, 4];
+        heap_sort(&mut v);
+        assert_eq!(v, vec![1, 3, 4, 4, 6, 8, 11, 13, 19]);
+
+        heap_sort_by(&mut v, |a, b| b.cmp(a));
+        assert_eq!(v, vec![19, 13, 11, 8, 6, 4, 4, 3, 1]);
+
+        let mut v = vec!["pete", "andy", "james"];
+        heap_sort_by_key(&mut v, |s| s.len());
+        assert_eq!(v, vec!["andy", "pete", "james"]);
+    }
+    #[test]
+    fn test_heap_sort_random() {
+        // cargo test test_heap_sort_random -- --nocapture
+        let mut r = RandGen::new(4);
+        for n in 0..200 {
+            let mut v: Vec<usize> = (0..n).map(|_| r.next_v(50)).collect();
+            let mut sorted_v = v.clone();
+            sorted_v.sort();
+            heap_sort(&mut v);
+            assert_eq!(v, sorted_v);
+        }
+    }
+    #[test]
+    fn test_heap_against_std() {
+        // cargo test test_heap_against_std -- --nocapture
+        let mut r = RandGen::new(17);
+        let mut mine = BinaryHeap::new();
+        let mut std_h = StdHeap::new();
+        for _ in 0..5_000 {
+            // push twice as often as pop so the heap grows
+            if r.next_v(3) == 0 {
+                assert_eq!(mine.pop(), std_h.pop());
+            } else {
+                let x = r.next_v(1_000);
+                mine.push(x);
+                std_h.push(x);
+            }
+            assert_eq!(mine.peek(), std_h.peek());
+            assert_eq!(mine.len(), std_h.len());
+        }
+        assert_eq!(mine.into_sorted_vec(), std_h.into_sorted_vec());
+    }
+    #[test]
+    fn test_min_heap_against_std() {
+        // cargo test test_min_heap_against_std -- --nocapture
+        let mut r = RandGen::new(23);
+        let v: Vec<usize> = (0..1_000).map(|_| r.next_v(100)).collect();
+
+        let mut mine = BinaryHeap::from_vec_min(v.clone());
+        let mut std_h: StdHeap<Reverse<usize>> = v.into_iter().map(Reverse).collect();
+        while let Some(Reverse(x)) = std_h.pop() {
+            assert_eq!(mine.pop(), Some(x));
+        }
+        assert!(mine.is_empty());
+        assert_eq!(mine.pop(), None);
+    }
+    #[test]
+    fn test_heap_comparator() {
+        // cargo test test_heap_comparator -- --nocapture
+        // a queue of jobs, lowest priority number first
+        let mut h = BinaryHeap::with_comparator(|a: &(u32, &str), b: &(u32, &str)| b.0.cmp(&a.0));
+        h.extend(vec![(3, "c"), (1, "a"), (2, "b")]);
+        assert_eq!(h.peek(), Some(&(1, "a")));
+        assert_eq!(h.pop(), Some((1, "a")));
+        assert_eq!(h.pop(), Some((2, "b")));
+        assert_eq!(h.pop(), Some((3, "c")));
+        assert_eq!(h.pop(), None);
+
+        let h: BinaryHeap<i32> = vec![5, 2, 8].into_iter().collect();
+        assert_eq!(h.into_sorted_vec(), vec![2, 5, 8]);
+    }
+}
@@ -0,0 +1,200 @@
+use std::cmp::Ordering;
+
+use crate::b_rand::{PivotRng, ThreadRng};
+use crate::heap::heap_sort_at;
+use crate::observer::{NoOp, SortObserver};
+use crate::pivot_at;
+
+// partitions this small are quicker with insertion sort
+const INSERTION_CUTOFF: usize = 16;
+
+// Quick sort that watches its own recursion depth
+// once the depth passes 2 log2(n) the pivots have been bad
+// so the partition is finished with heap sort instead
+// worst case O(n ln(n))
+pub fn intro_sort<T: PartialOrd>(v: &mut [T]) {
+    intro_sort_observed(v, &NoOp);
+}
+
+pub fn intro_sort_observed<T: PartialOrd, O: SortObserver<T>>(v: &mut [T], obs: &O) {
+    let limit = depth_limit(v.len());
+    intro_sort_at(
+        v,
+        0,
+        0,
+        limit,
+        &mut ThreadRng,
+        &mut |a: &T, b: &T| a < b,
+        obs,
+    );
+}
+
+pub fn intro_sort_with_rng<T: PartialOrd, R: PivotRng>(v: &mut [T], rng: &mut R) {
+    let limit = depth_limit(v.len());
+    intro_sort_at(v, 0, 0, limit, rng, &mut |a: &T, b: &T| a < b, &NoOp);
+}
+
+pub fn intro_sort_by<T, F>(v: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let limit = depth_limit(v.len());
+    intro_sort_at(
+        v,
+        0,
+        0,
+        limit,
+        &mut ThreadRng,
+        &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    );
+}
+
+pub fn intro_sort_by_key<T, K, F>(v: &mut [T], mut f: F)
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    let limit = depth_limit(v.len());
+    intro_sort_at(
+        v,
+        0,
+        0,
+        limit,
+        &mut ThreadRng,
+        &mut |a: &T, b: &T| f(a) < f(b),
+        &NoOp,
+    );
+}
+
+// 2 * log2(n) rounded up
+pub(crate) fn depth_limit(n: usize) -> usize {
+    2 * (usize::BITS - n.leading_zeros()) as usize
+}
+
+pub(crate) fn intro_sort_at<T, R, F, O>(
+    v: &mut [T],
+    off: usize,
+    depth: usize,
+    limit: usize,
+    rng: &mut R,
+    is_less: &mut F,
+    obs: &O,
+) where
+    R: PivotRng,
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    obs.depth(depth);
+    if v.len() <= INSERTION_CUTOFF {
+        insertion_sort_at(v, off, is_less, obs);
+        return;
+    }
+    if depth >= limit {
+        heap_sort_at(v, off, is_less, obs);
+        return;
+    }
+
+    let p = pivot_at(v, off, rng, is_less, obs);
+    obs.pass(v);
+
+    let (a, b) = v.split_at_mut(p);
+    intro_sort_at(a, off, depth + 1, limit, rng, is_less, obs);
+    intro_sort_at(
+        &mut b[1..],
+        off + p + 1,
+        depth + 1,
+        limit,
+        rng,
+        is_less,
+        obs,
+    );
+}
+
+// Big O n^2 but very little overhead on short or nearly sorted runs
+pub(crate) fn insertion_sort_at<T, F, O>(v: &mut [T], off: usize, is_less: &mut F, obs: &O)
+where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    for i in 1..v.len() {
+        // walk the new element back until it is not less than its neighbour
+        let mut j = i;
+        while j > 0 {
+            obs.compare(off + j, off + j - 1);
+            if !is_less(&v[j], &v[j - 1]) {
+                break;
+            }
+            obs.swap(off + j - 1, off + j);
+            v.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observer::Counter;
+
+    fn is_sorted<T: PartialOrd>(v: &[T]) -> bool {
+        v.windows(2).all(|w| w[0] <= w[1])
+    }
+
+    // check the result and that the work stayed n ln(n)
+    fn check_intro_sort(mut v: Vec<i32>) {
+        let n = v.len();
+        let c = Counter::new();
+        intro_sort_observed(&mut v, &c);
+        assert!(is_sorted(&v));
+
+        let t = c.totals();
+        println!("n:{} {}", n, t);
+        assert!(t.max_depth <= depth_limit(n));
+        let log_n = (usize::BITS - n.leading_zeros()) as usize;
+        assert!(t.comparisons <= 8 * n * log_n);
+    }
+
+    #[test]
+    fn test_intro_sort() {
+        // cargo test test_intro_sort -- --nocapture
+        let mut v = vec![4, 6, 1, 8, 11, 13, 3];
+        intro_sort(&mut v);
+
+        let sorted_v = vec![1, 3, 4, 6, 8, 11, 13];
+        assert_eq!(v, sorted_v);
+    }
+    #[test]
+    fn test_intro_sort_sorted() {
+        // cargo test test_intro_sort_sorted -- --nocapture
+        check_intro_sort((0..10_000).collect());
+    }
+    #[test]
+    fn test_intro_sort_reversed() {
+        // cargo test test_intro_sort_reversed -- --nocapture
+        check_intro_sort((0..10_000).rev().collect());
+    }
+    #[test]
+    fn test_intro_sort_all_equal() {
+        // cargo test test_intro_sort_all_equal -- --nocapture
+        // every pivot lands at 0 here, so this has to hit the heap sort
+        check_intro_sort(vec![7; 10_000]);
+    }
+    #[test]
+    fn test_intro_sort_organ_pipe() {
+        // cargo test test_intro_sort_organ_pipe -- --nocapture
+        let v: Vec<i32> = (0..5_000).chain((0..5_000).rev()).collect();
+        check_intro_sort(v);
+    }
+    #[test]
+    fn test_intro_sort_by() {
+        // cargo test test_intro_sort_by -- --nocapture
+        let mut v: Vec<i32> = (0..100).collect();
+        intro_sort_by(&mut v, |a, b| b.cmp(a));
+        assert_eq!(v, (0..100).rev().collect::<Vec<_>>());
+
+        let mut v: Vec<(i32, char)> = (0..100).map(|x| (x % 7, 'a')).collect();
+        intro_sort_by_key(&mut v, |p| p.0);
+        assert!(is_sorted(&v));
+    }
+}
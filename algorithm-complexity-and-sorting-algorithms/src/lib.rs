@@ -1,9 +1,82 @@
-use std::fmt::Debug;
+use std::cmp::Ordering;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 
-mod b_rand;
+pub mod b_rand;
+pub mod external;
+#[cfg(test)]
+mod harness;
+pub mod heap;
+pub mod introsort;
+pub mod merge;
+pub mod merge_iter;
+pub mod observer;
+pub mod radix;
+pub mod rand_stats;
+pub mod search;
+pub mod select;
+pub mod simple;
+pub mod sorter;
+pub mod three_way;
+pub mod timsort;
+
+pub use b_rand::{
+    MedianOfThree, Pcg32, PivotRng, RandGen, Random, ThreadRng, XorShift, XorShiftStar,
+};
+pub use external::{external_sort, external_sort_by, external_sort_by_key, ExternalError};
+pub use heap::{heap_sort, BinaryHeap};
+pub use introsort::{intro_sort, intro_sort_by, intro_sort_by_key, intro_sort_observed};
+pub use merge::{
+    merge_sort_bottom_up, merge_sort_rayon, merge_sort_slice, merge_sort_slice_with_buf,
+};
+pub use merge_iter::{merge_sorted, merge_two, MergeSorted, MergeTwo};
+pub use observer::{Counter, Counts, Event, NoOp, Recorder, SortObserver, Tracer};
+pub use radix::{counting_sort, msd_radix_sort, radix_sort, RadixKey};
+pub use rand_stats::{chi_squared, period, serial_correlation, ChiSquared, Report};
+pub use search::{
+    binary_search, binary_search_by, equal_range, exponential_search, interpolation_search,
+    lower_bound, upper_bound,
+};
+pub use select::{bottom_k, median, median_of_medians, select_nth, top_k};
+pub use simple::{cocktail_sort, insertion_sort, selection_sort, shell_sort};
+pub use sorter::{int_sorters, sorter, sorters, Complexity, SortInfo, Sorter};
+pub use three_way::{pivot_3way, quick_sort_3way, quick_sort_3way_rayon};
+pub use timsort::{tim_sort, tim_sort_by, tim_sort_by_key, tim_sort_observed};
 
 // Big O n^2
-pub fn bubble_sort<T: PartialOrd + Debug>(v: &mut [T]) {
+pub fn bubble_sort<T: PartialOrd>(v: &mut [T]) {
+    bubble_sort_observed(v, &NoOp);
+}
+
+pub fn bubble_sort_observed<T: PartialOrd, O: SortObserver<T>>(v: &mut [T], obs: &O) {
+    bubble_sort_impl(v, &mut |a: &T, b: &T| a < b, obs);
+}
+
+pub fn bubble_sort_by<T, F>(v: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    bubble_sort_impl(
+        v,
+        &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    );
+}
+
+pub fn bubble_sort_by_key<T, K, F>(v: &mut [T], mut f: F)
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    bubble_sort_impl(v, &mut |a: &T, b: &T| f(a) < f(b), &NoOp);
+}
+
+// every sort is written against is_less
+// so PartialOrd, comparators and keys all share one body
+fn bubble_sort_impl<T, F, O>(v: &mut [T], is_less: &mut F, obs: &O)
+where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
     for p in 0..v.len() {
         // optimization check for a sorted list
         let mut sorted = true;
@@ -11,11 +84,13 @@ pub fn bubble_sort<T: PartialOrd + Debug>(v: &mut [T]) {
         // optimization after first pass
         // the biggest value bubbles to the end
         for i in 0..(v.len() - 1) - p {
-            if v[i] > v[i + 1] {
+            obs.compare(i, i + 1);
+            if is_less(&v[i + 1], &v[i]) {
+                obs.swap(i, i + 1);
                 v.swap(i, i + 1);
                 sorted = false;
             }
-            println!("pass:{},{} {:?}", p, i, v);
+            obs.pass(v);
         }
 
         // optimization check for a sorted list
@@ -26,49 +101,64 @@ pub fn bubble_sort<T: PartialOrd + Debug>(v: &mut [T]) {
 }
 
 // right O(n ln(n))
-pub fn merge_sort<T: PartialOrd + Debug>(mut v: Vec<T>) -> Vec<T> {
-    println!("Merge sort: {:?}", v);
+pub fn merge_sort<T: PartialOrd>(v: Vec<T>) -> Vec<T> {
+    merge_sort_observed(v, &NoOp)
+}
+
+pub fn merge_sort_observed<T: PartialOrd, O: SortObserver<T>>(v: Vec<T>, obs: &O) -> Vec<T> {
+    merge_sort_at(v, 0, 0, &mut |a: &T, b: &T| a < b, obs)
+}
+
+pub fn merge_sort_by<T, F>(v: Vec<T>, mut compare: F) -> Vec<T>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    merge_sort_at(
+        v,
+        0,
+        0,
+        &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    )
+}
+
+pub fn merge_sort_by_key<T, K, F>(v: Vec<T>, mut f: F) -> Vec<T>
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    merge_sort_at(v, 0, 0, &mut |a: &T, b: &T| f(a) < f(b), &NoOp)
+}
+
+// off is where v starts in the top level vec, so events get real indices
+fn merge_sort_at<T, F, O>(
+    mut v: Vec<T>,
+    off: usize,
+    depth: usize,
+    is_less: &mut F,
+    obs: &O,
+) -> Vec<T>
+where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    obs.depth(depth);
+    obs.pass(&v);
     if v.len() <= 1 {
         return v;
     }
 
     let mut res = Vec::with_capacity(v.len());
-    let b = v.split_off(v.len() / 2);
-    let a = merge_sort(v);
-    let b = merge_sort(b);
-
-    let mut a_it = a.into_iter();
-    let mut b_it = b.into_iter();
-    let mut a_peek = a_it.next();
-    let mut b_peek = b_it.next();
-
-    loop {
-        match a_peek {
-            Some(ref a_val) => match b_peek {
-                Some(ref b_val) => {
-                    if b_val < a_val {
-                        res.push(b_peek.take().unwrap());
-                        b_peek = b_it.next();
-                    } else {
-                        res.push(a_peek.take().unwrap());
-                        a_peek = a_it.next();
-                    }
-                }
-                None => {
-                    res.push(a_peek.take().unwrap());
-                    res.extend(a_it);
-                    return res;
-                }
-            },
-            None => {
-                if let Some(b_val) = b_peek {
-                    res.push(b_val);
-                }
-                res.extend(b_it);
-                return res;
-            }
-        }
+    let mid = v.len() / 2;
+    let b = v.split_off(mid);
+    let a = merge_sort_at(v, off, depth + 1, is_less, obs);
+    let b = merge_sort_at(b, off + mid, depth + 1, is_less, obs);
+
+    let mut merged = MergeTwo::new(a.into_iter(), b.into_iter(), is_less);
+    while let Some(t) = merged.next_at(&mut |a_n, b_n| obs.compare(off + a_n, off + mid + b_n)) {
+        res.push(t);
     }
+    res
 }
 
 // Move the 1st element to the correct place
@@ -76,78 +166,302 @@ pub fn merge_sort<T: PartialOrd + Debug>(mut v: Vec<T>) -> Vec<T> {
 // else should be after
 // output is the pivot's location
 pub fn pivot<T: PartialOrd>(v: &mut [T]) -> usize {
-    let mut p = b_rand::rand(v.len());
+    pivot_with_rng(v, &mut ThreadRng)
+}
+
+pub fn pivot_with_rng<T: PartialOrd, R: PivotRng>(v: &mut [T], rng: &mut R) -> usize {
+    pivot_at(v, 0, rng, &mut |a: &T, b: &T| a < b, &NoOp)
+}
+
+pub fn pivot_by<T, F>(v: &mut [T], mut compare: F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    pivot_at(
+        v,
+        0,
+        &mut ThreadRng,
+        &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    )
+}
+
+fn pivot_at<T, R, F, O>(v: &mut [T], off: usize, rng: &mut R, is_less: &mut F, obs: &O) -> usize
+where
+    R: PivotRng,
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    let mut p = rng.pivot(v, is_less);
+    obs.swap(off + p, off);
     v.swap(p, 0);
     p = 0;
 
     for i in 1..v.len() {
-        if v[i] < v[p] {
+        obs.compare(off + i, off + p);
+        if is_less(&v[i], &v[p]) {
             // move our pivot forward 1, and put this element before it
+            obs.swap(off + p + 1, off + i);
             v.swap(p + 1, i);
+            obs.swap(off + p, off + p + 1);
             v.swap(p, p + 1);
             p += 1;
         }
     }
+    obs.partition(off, off + v.len(), off + p);
     p
 }
 
-pub fn quick_sort<T: PartialOrd + Debug>(v: &mut [T]) {
+pub fn quick_sort<T: PartialOrd>(v: &mut [T]) {
+    quick_sort_observed(v, &NoOp);
+}
+
+pub fn quick_sort_observed<T: PartialOrd, O: SortObserver<T>>(v: &mut [T], obs: &O) {
+    quick_sort_at(v, 0, 0, &mut ThreadRng, &mut |a: &T, b: &T| a < b, obs);
+}
+
+// pivots come from rng, seed one for a repeatable sort
+pub fn quick_sort_with_rng<T: PartialOrd, R: PivotRng>(v: &mut [T], rng: &mut R) {
+    quick_sort_at(v, 0, 0, rng, &mut |a: &T, b: &T| a < b, &NoOp);
+}
+
+pub fn quick_sort_by<T, F>(v: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    quick_sort_at(
+        v,
+        0,
+        0,
+        &mut ThreadRng,
+        &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    );
+}
+
+pub fn quick_sort_by_key<T, K, F>(v: &mut [T], mut f: F)
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    quick_sort_at(
+        v,
+        0,
+        0,
+        &mut ThreadRng,
+        &mut |a: &T, b: &T| f(a) < f(b),
+        &NoOp,
+    );
+}
+
+fn quick_sort_at<T, R, F, O>(
+    v: &mut [T],
+    off: usize,
+    depth: usize,
+    rng: &mut R,
+    is_less: &mut F,
+    obs: &O,
+) where
+    R: PivotRng,
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    obs.depth(depth);
     if v.len() <= 1 {
         return;
     }
-    let p = pivot(v);
-    println!("{:?}", v);
+    let p = pivot_at(v, off, rng, is_less, obs);
+    obs.pass(v);
 
     let (a, b) = v.split_at_mut(p);
-    quick_sort(a);
-    quick_sort(&mut b[1..]);
+    quick_sort_at(a, off, depth + 1, rng, is_less, obs);
+    quick_sort_at(&mut b[1..], off + p + 1, depth + 1, rng, is_less, obs);
 }
 
-pub fn threaded_quick_sort<T: 'static + PartialOrd + Debug + Send>(v: &mut [T]) {
-    if v.len() <= 1 {
+// partitions smaller than this are not worth a thread
+pub const THREAD_CUTOFF: usize = 10_000;
+
+// Quick sort that hands one side of each partition to a new thread
+// threads are scoped so v can be borrowed, no 'static or unsafe needed.
+// At most one thread per core is spawned over the whole sort
+pub fn threaded_quick_sort<T: PartialOrd + Send>(v: &mut [T]) {
+    threaded_quick_sort_observed(v, &NoOp);
+}
+
+pub fn threaded_quick_sort_observed<T, O>(v: &mut [T], obs: &O)
+where
+    T: PartialOrd + Send,
+    O: SortObserver<T> + Sync,
+{
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4);
+    let budget = ThreadBudget::new(threads, THREAD_CUTOFF);
+    threaded_quick_sort_at(v, 0, 0, &budget, &mut |a: &T, b: &T| a < b, obs);
+}
+
+// max_threads is the most threads spawned over the whole sort
+// partitions shorter than cutoff are sorted on the current thread
+pub fn threaded_quick_sort_with<T: PartialOrd + Send>(
+    v: &mut [T],
+    max_threads: usize,
+    cutoff: usize,
+) {
+    let budget = ThreadBudget::new(max_threads, cutoff);
+    threaded_quick_sort_at(v, 0, 0, &budget, &mut |a: &T, b: &T| a < b, &NoOp);
+}
+
+// How many more threads the sort may start
+// shared by every thread of one sort
+struct ThreadBudget {
+    left: AtomicUsize,
+    cutoff: usize,
+}
+
+impl ThreadBudget {
+    fn new(max_threads: usize, cutoff: usize) -> Self {
+        ThreadBudget {
+            left: AtomicUsize::new(max_threads),
+            cutoff,
+        }
+    }
+
+    // claim a thread for a partition of len, false if too small or none left
+    fn take(&self, len: usize) -> bool {
+        len >= self.cutoff
+            && self
+                .left
+                .fetch_update(AtomicOrdering::SeqCst, AtomicOrdering::SeqCst, |n| {
+                    n.checked_sub(1)
+                })
+                .is_ok()
+    }
+}
+
+fn threaded_quick_sort_at<T, F, O>(
+    v: &mut [T],
+    off: usize,
+    depth: usize,
+    budget: &ThreadBudget,
+    is_less: &mut F,
+    obs: &O,
+) where
+    T: Send,
+    F: FnMut(&T, &T) -> bool + Clone + Send,
+    O: SortObserver<T> + Sync,
+{
+    // pivots come from each thread's own generator.
+    // a cutoff of 0 or 1 still has to stop at empty partitions
+    if v.len() <= 1 || v.len() < budget.cutoff {
+        quick_sort_at(v, off, depth, &mut ThreadRng, is_less, obs);
         return;
     }
+    obs.depth(depth);
 
-    let p = pivot(v);
-    println!("{:?}", v);
+    let p = pivot_at(v, off, &mut ThreadRng, is_less, obs);
+    obs.pass(v);
 
     let (a, b) = v.split_at_mut(p);
+    let b = &mut b[1..];
+
+    if budget.take(a.len()) {
+        // the new thread gets its own copy of is_less
+        let mut a_less = is_less.clone();
+        std::thread::scope(|s| {
+            s.spawn(|| threaded_quick_sort_at(a, off, depth + 1, budget, &mut a_less, obs));
+            threaded_quick_sort_at(b, off + p + 1, depth + 1, budget, is_less, obs);
+        });
+    } else {
+        threaded_quick_sort_at(a, off, depth + 1, budget, is_less, obs);
+        threaded_quick_sort_at(b, off + p + 1, depth + 1, budget, is_less, obs);
+    }
+}
 
-    // explicit lifetime required in the type of `v`
-    // lifetime `'static` requiredrustc(E0621)
-    // lib.rs(106, 61): add explicit lifetime `'static` to the type of `v`
-
-    struct RawSend<T>(*mut [T]); // one element tuple
-    unsafe impl<T> Send for RawSend<T> {}
+pub fn quick_sort_rayon<T: Send + PartialOrd>(v: &mut [T]) {
+    quick_sort_rayon_observed(v, &NoOp);
+}
 
-    let raw_a: *mut [T] = a as *mut [T];
-    let raw_s = RawSend(raw_a);
+pub fn quick_sort_rayon_observed<T, O>(v: &mut [T], obs: &O)
+where
+    T: Send + PartialOrd,
+    O: SortObserver<T> + Sync,
+{
+    quick_sort_rayon_at(v, 0, 0, &mut ThreadRng, &|a: &T, b: &T| a < b, obs);
+}
 
-    // we call join in unsafe
-    unsafe {
-        let handle = std::thread::spawn(move || {
-            threaded_quick_sort(&mut *raw_s.0);
-        });
+// each task forked by rayon::join gets a clone of rng
+pub fn quick_sort_rayon_with_rng<T, R>(v: &mut [T], rng: &mut R)
+where
+    T: Send + PartialOrd,
+    R: PivotRng + Clone + Send,
+{
+    quick_sort_rayon_at(v, 0, 0, rng, &|a: &T, b: &T| a < b, &NoOp);
+}
 
-        threaded_quick_sort(&mut b[1..]);
+// the comparator is shared between threads so it must be Fn + Sync
+pub fn quick_sort_rayon_by<T, F>(v: &mut [T], compare: F)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    quick_sort_rayon_at(
+        v,
+        0,
+        0,
+        &mut ThreadRng,
+        &|a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    );
+}
 
-        handle.join().ok();
-    }
+pub fn quick_sort_rayon_by_key<T, K, F>(v: &mut [T], f: F)
+where
+    T: Send,
+    K: PartialOrd,
+    F: Fn(&T) -> K + Sync,
+{
+    quick_sort_rayon_at(v, 0, 0, &mut ThreadRng, &|a: &T, b: &T| f(a) < f(b), &NoOp);
 }
 
-pub fn quick_sort_rayon<T: Send + PartialOrd + Debug>(v: &mut [T]) {
+fn quick_sort_rayon_at<T, R, F, O>(
+    v: &mut [T],
+    off: usize,
+    depth: usize,
+    rng: &mut R,
+    is_less: &F,
+    obs: &O,
+) where
+    T: Send,
+    R: PivotRng + Clone + Send,
+    F: Fn(&T, &T) -> bool + Sync,
+    O: SortObserver<T> + Sync,
+{
+    obs.depth(depth);
     if v.len() <= 1 {
         return;
     }
-    let p = pivot(v);
-    println!("{:?}", v);
+    let p = pivot_at(v, off, rng, &mut |a: &T, b: &T| is_less(a, b), obs);
+    obs.pass(v);
 
     let (a, b) = v.split_at_mut(p);
+    let mut b_rng = rng.clone();
 
     // puts the 2nd fn on a queue then start the 1st fn
     // if another thread is ready it will steal the 2nd fn
     // this works recursively down the stack
-    rayon::join(|| quick_sort_rayon(a), || quick_sort_rayon(&mut b[1..]));
+    rayon::join(
+        || quick_sort_rayon_at(a, off, depth + 1, rng, is_less, obs),
+        || {
+            quick_sort_rayon_at(
+                &mut b[1..],
+                off + p + 1,
+                depth + 1,
+                &mut b_rng,
+                is_less,
+                obs,
+            )
+        },
+    );
 }
 
 #[cfg(test)]
@@ -240,4 +554,280 @@ mod tests {
         let sorted_v = vec![1, 2, 6, 7, 9, 12, 13, 14];
         assert_eq!(v, sorted_v);
     }
+
+    #[test]
+    fn test_counter_bubble_sort_sorted() {
+        // cargo test test_counter_bubble_sort_sorted -- --nocapture
+        // a sorted list takes one pass of n-1 comparisons
+        let mut v = vec![1, 2, 6, 7, 9, 12, 13, 14];
+        let c = Counter::new();
+        bubble_sort_observed(&mut v, &c);
+
+        let t = c.totals();
+        println!("{}", t);
+        assert_eq!(t.comparisons, v.len() - 1);
+        assert_eq!(t.swaps, 0);
+    }
+    #[test]
+    fn test_counter_quick_sort() {
+        // cargo test test_counter_quick_sort -- --nocapture
+        let mut v: Vec<i32> = (0..100).rev().collect();
+        let c = Counter::new();
+        quick_sort_observed(&mut v, &c);
+
+        let sorted_v: Vec<i32> = (0..100).collect();
+        assert_eq!(v, sorted_v);
+
+        let t = c.totals();
+        println!("{}", t);
+        assert!(t.partitions > 0);
+        assert!(t.max_depth > 0);
+        // never worse than n^2 / 2
+        assert!(t.comparisons <= 100 * 99 / 2);
+    }
+    #[test]
+    fn test_counter_parallel_sorts() {
+        // cargo test test_counter_parallel_sorts -- --nocapture
+        let sorted_v: Vec<i32> = (0..200).collect();
+        for f in &[
+            threaded_quick_sort_observed::<i32, Counter>,
+            quick_sort_rayon_observed,
+        ] {
+            let mut v: Vec<i32> = (0..200).rev().collect();
+            let c = Counter::new();
+            f(&mut v, &c);
+            assert_eq!(v, sorted_v);
+            assert!(c.totals().comparisons >= 199);
+        }
+    }
+    #[test]
+    fn test_counter_merge_sort() {
+        // cargo test test_counter_merge_sort -- --nocapture
+        let c = Counter::new();
+        let v = merge_sort_observed(vec![4, 6, 1, 8, 11, 13, 3], &c);
+        assert_eq!(v, vec![1, 3, 4, 6, 8, 11, 13]);
+
+        let t = c.totals();
+        // depth is ln(n) rounded up
+        assert_eq!(t.max_depth, 3);
+        assert!(t.comparisons > 0);
+    }
+    #[test]
+    fn test_tracer() {
+        // cargo test test_tracer -- --nocapture
+        let mut v = vec![4, 6, 1, 8, 11, 13, 3];
+        quick_sort_observed(&mut v, &Tracer);
+        assert_eq!(v, vec![1, 3, 4, 6, 8, 11, 13]);
+    }
+
+    #[derive(Debug, PartialEq, Clone)]
+    struct Person {
+        name: &'static str,
+        age: u32,
+    }
+
+    fn people() -> Vec<Person> {
+        vec![
+            Person {
+                name: "james",
+                age: 18,
+            },
+            Person {
+                name: "dave",
+                age: 45,
+            },
+            Person {
+                name: "andy",
+                age: 23,
+            },
+            Person {
+                name: "pete",
+                age: 18,
+            },
+            Person {
+                name: "jane",
+                age: 45,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_sort_by_descending() {
+        // cargo test test_sort_by_descending -- --nocapture
+        let sorted_v = vec![13, 11, 8, 6, 4, 3, 1];
+
+        let mut v = vec![4, 6, 1, 8, 11, 13, 3];
+        bubble_sort_by(&mut v, |a, b| b.cmp(a));
+        assert_eq!(v, sorted_v);
+
+        let v = merge_sort_by(vec![4, 6, 1, 8, 11, 13, 3], |a, b| b.cmp(a));
+        assert_eq!(v, sorted_v);
+
+        let mut v = vec![4, 6, 1, 8, 11, 13, 3];
+        quick_sort_by(&mut v, |a, b| b.cmp(a));
+        assert_eq!(v, sorted_v);
+
+        let mut v = vec![4, 6, 1, 8, 11, 13, 3];
+        quick_sort_rayon_by(&mut v, |a, b| b.cmp(a));
+        assert_eq!(v, sorted_v);
+    }
+    #[test]
+    fn test_sort_by_key() {
+        // cargo test test_sort_by_key -- --nocapture
+        let ages = vec![18, 18, 23, 45, 45];
+
+        let mut v = people();
+        bubble_sort_by_key(&mut v, |p| p.age);
+        assert_eq!(v.iter().map(|p| p.age).collect::<Vec<_>>(), ages);
+
+        let v = merge_sort_by_key(people(), |p| p.age);
+        assert_eq!(v.iter().map(|p| p.age).collect::<Vec<_>>(), ages);
+
+        let mut v = people();
+        quick_sort_by_key(&mut v, |p| p.age);
+        assert_eq!(v.iter().map(|p| p.age).collect::<Vec<_>>(), ages);
+
+        let mut v = people();
+        quick_sort_rayon_by_key(&mut v, |p| p.name);
+        let names: Vec<_> = v.iter().map(|p| p.name).collect();
+        assert_eq!(names, vec!["andy", "dave", "james", "jane", "pete"]);
+    }
+    #[test]
+    fn test_stable_sort_by_key() {
+        // cargo test test_stable_sort_by_key -- --nocapture
+        // bubble and merge keep equal keys in their input order
+        let names = vec!["james", "pete", "andy", "dave", "jane"];
+
+        let mut v = people();
+        bubble_sort_by_key(&mut v, |p| p.age);
+        assert_eq!(v.iter().map(|p| p.name).collect::<Vec<_>>(), names);
+
+        let v = merge_sort_by_key(people(), |p| p.age);
+        assert_eq!(v.iter().map(|p| p.name).collect::<Vec<_>>(), names);
+    }
+    #[test]
+    fn test_pivot_by() {
+        // cargo test test_pivot_by -- --nocapture
+        let mut v = vec![4, 6, 1, 19, 8, 11, 13, 3];
+        let p = pivot_by(&mut v, |a, b| b.cmp(a));
+
+        for x in 0..v.len() {
+            assert!((v[x] > v[p]) == (x < p))
+        }
+    }
+
+    #[test]
+    fn test_threaded_quick_sort_borrowed() {
+        // cargo test test_threaded_quick_sort_borrowed -- --nocapture
+        // no 'static needed, so borrowed data sorts fine
+        let names = ["pete".to_string(), "andy".to_string(), "james".to_string()];
+        let mut v: Vec<&str> = names.iter().map(|s| s.as_str()).collect();
+        threaded_quick_sort_with(&mut v, 4, 1);
+        assert_eq!(v, vec!["andy", "james", "pete"]);
+    }
+    #[test]
+    fn test_thread_budget() {
+        // cargo test test_thread_budget -- --nocapture
+        let budget = ThreadBudget::new(3, 100);
+        assert!(!budget.take(99));
+        assert!(budget.take(100));
+        assert!(budget.take(1000));
+        assert!(budget.take(1000));
+        assert!(!budget.take(1000));
+
+        // a sort can never spawn past its budget
+        let mut v: Vec<u64> = (0..100_000).map(|x| (x * 7_919) % 100_003).collect();
+        let budget = ThreadBudget::new(5, 1_000);
+        threaded_quick_sort_at(&mut v, 0, 0, &budget, &mut |a: &u64, b: &u64| a < b, &NoOp);
+        assert!(v.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(budget.left.load(AtomicOrdering::SeqCst), 0);
+    }
+    #[test]
+    fn test_threaded_quick_sort_small_cutoff() {
+        // cargo test test_threaded_quick_sort_small_cutoff -- --nocapture
+        for cutoff in 0..3 {
+            let mut v: Vec<u64> = (0..1_000).map(|x| (x * 7_919) % 1_009).collect();
+            threaded_quick_sort_with(&mut v, 4, cutoff);
+            assert!(v.windows(2).all(|w| w[0] <= w[1]), "cutoff {}", cutoff);
+            let mut e: Vec<u64> = vec![];
+            threaded_quick_sort_with(&mut e, 4, cutoff);
+            let mut one = vec![5];
+            threaded_quick_sort_with(&mut one, 4, cutoff);
+            assert_eq!(one, vec![5]);
+        }
+    }
+    #[test]
+    fn test_threaded_quick_sort_stress() {
+        // cargo test test_threaded_quick_sort_stress --release -- --nocapture
+        // millions of elements, one thread per level would be thousands of threads
+        let mut r = b_rand::RandGen::new(99);
+        let mut v: Vec<usize> = (0..2_000_000).map(|_| r.next_v(1_000_000_000)).collect();
+        let mut sorted_v = v.clone();
+        sorted_v.sort_unstable();
+
+        threaded_quick_sort_with(&mut v, 16, 1_000);
+        assert_eq!(v, sorted_v);
+    }
+
+    #[test]
+    fn test_quick_sort_with_rng() {
+        // cargo test test_quick_sort_with_rng -- --nocapture
+        let v: Vec<u32> = (0..1_000).map(|x| (x * 7_919) % 1_009).collect();
+        let mut sorted_v = v.clone();
+        sorted_v.sort();
+
+        // the same seed gives the same pivots and the same work
+        let (c1, c2) = (Counter::new(), Counter::new());
+        let mut a = v.clone();
+        quick_sort_at(
+            &mut a,
+            0,
+            0,
+            &mut XorShift::new(5),
+            &mut |a: &u32, b: &u32| a < b,
+            &c1,
+        );
+        let mut b = v.clone();
+        quick_sort_at(
+            &mut b,
+            0,
+            0,
+            &mut XorShift::new(5),
+            &mut |a: &u32, b: &u32| a < b,
+            &c2,
+        );
+        assert_eq!(a, sorted_v);
+        assert_eq!(b, sorted_v);
+        assert_eq!(c1.totals(), c2.totals());
+
+        let mut a = v.clone();
+        quick_sort_with_rng(&mut a, &mut RandGen::new(3));
+        assert_eq!(a, sorted_v);
+
+        let mut a = v.clone();
+        quick_sort_rayon_with_rng(&mut a, &mut XorShift::new(3));
+        assert_eq!(a, sorted_v);
+    }
+    #[test]
+    fn test_median_of_three_sorted() {
+        // cargo test test_median_of_three_sorted -- --nocapture
+        // sorted input, the middle is always the median, so depth is log2(n)
+        let mut v: Vec<u32> = (0..1_023).collect();
+        let c = Counter::new();
+        quick_sort_at(
+            &mut v,
+            0,
+            0,
+            &mut MedianOfThree,
+            &mut |a: &u32, b: &u32| a < b,
+            &c,
+        );
+        assert_eq!(v, (0..1_023).collect::<Vec<_>>());
+        println!("{}", c.totals());
+        assert!(c.totals().max_depth <= 11);
+
+        let mut v: Vec<u32> = (0..1_023).rev().collect();
+        quick_sort_with_rng(&mut v, &mut MedianOfThree);
+        assert_eq!(v, (0..1_023).collect::<Vec<_>>());
+    }
 }
@@ -0,0 +1,614 @@
+use std::cmp::Ordering;
+use std::ptr;
+
+use crate::observer::{NoOp, SortObserver};
+
+// Merge sort on a slice in place
+// unlike merge_sort this allocates one scratch buffer of n/2
+// for the whole sort rather than new vecs at every level
+// stable, O(n ln(n))
+pub fn merge_sort_slice<T: PartialOrd>(v: &mut [T]) {
+    merge_sort_slice_with_buf(v, &mut Vec::new());
+}
+
+pub fn merge_sort_slice_observed<T: PartialOrd, O: SortObserver<T>>(v: &mut [T], obs: &O) {
+    merge_sort_slice_impl(v, &mut Vec::new(), &mut |a: &T, b: &T| a < b, obs);
+}
+
+pub fn merge_sort_slice_by<T, F>(v: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    merge_sort_slice_impl(
+        v,
+        &mut Vec::new(),
+        &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    );
+}
+
+pub fn merge_sort_slice_by_key<T, K, F>(v: &mut [T], mut f: F)
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    merge_sort_slice_impl(v, &mut Vec::new(), &mut |a: &T, b: &T| f(a) < f(b), &NoOp);
+}
+
+// buf is cleared and only its capacity is used
+// keep it around between calls and no further allocation happens
+pub fn merge_sort_slice_with_buf<T: PartialOrd>(v: &mut [T], buf: &mut Vec<T>) {
+    merge_sort_slice_impl(v, buf, &mut |a: &T, b: &T| a < b, &NoOp);
+}
+
+fn merge_sort_slice_impl<T, F, O>(v: &mut [T], buf: &mut Vec<T>, is_less: &mut F, obs: &O)
+where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    if std::mem::size_of::<T>() == 0 {
+        return;
+    }
+    buf.clear();
+    buf.reserve(v.len() / 2);
+    merge_sort_slice_at(v, 0, 0, buf.as_mut_ptr(), is_less, obs);
+}
+
+fn merge_sort_slice_at<T, F, O>(
+    v: &mut [T],
+    off: usize,
+    depth: usize,
+    buf: *mut T,
+    is_less: &mut F,
+    obs: &O,
+) where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    obs.depth(depth);
+    if v.len() <= 1 {
+        return;
+    }
+    let mid = v.len() / 2;
+    merge_sort_slice_at(&mut v[..mid], off, depth + 1, buf, is_less, obs);
+    merge_sort_slice_at(&mut v[mid..], off + mid, depth + 1, buf, is_less, obs);
+    // buf was reserved for n/2 and neither run is longer than that
+    unsafe { merge_runs(v, mid, off, buf, is_less, obs) };
+    obs.pass(v);
+}
+
+// Bottom up merge sort, no recursion
+// merges runs of width 1, then 2, 4 ... until one run covers v
+pub fn merge_sort_bottom_up<T: PartialOrd>(v: &mut [T]) {
+    merge_sort_bottom_up_with_buf(v, &mut Vec::new());
+}
+
+pub fn merge_sort_bottom_up_observed<T: PartialOrd, O: SortObserver<T>>(v: &mut [T], obs: &O) {
+    merge_sort_bottom_up_impl(v, &mut Vec::new(), &mut |a: &T, b: &T| a < b, obs);
+}
+
+pub fn merge_sort_bottom_up_by<T, F>(v: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    merge_sort_bottom_up_impl(
+        v,
+        &mut Vec::new(),
+        &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    );
+}
+
+pub fn merge_sort_bottom_up_with_buf<T: PartialOrd>(v: &mut [T], buf: &mut Vec<T>) {
+    merge_sort_bottom_up_impl(v, buf, &mut |a: &T, b: &T| a < b, &NoOp);
+}
+
+fn merge_sort_bottom_up_impl<T, F, O>(v: &mut [T], buf: &mut Vec<T>, is_less: &mut F, obs: &O)
+where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    if std::mem::size_of::<T>() == 0 {
+        return;
+    }
+    let len = v.len();
+    buf.clear();
+    buf.reserve(len / 2);
+    let buf = buf.as_mut_ptr();
+
+    let mut width = 1;
+    let mut level = 0;
+    while width < len {
+        obs.depth(level);
+        let mut lo = 0;
+        // only merge where there is a right hand run
+        while lo + width < len {
+            let hi = std::cmp::min(lo + 2 * width, len);
+            // the shorter run is at most half of hi - lo
+            unsafe { merge_runs(&mut v[lo..hi], width, lo, buf, is_less, obs) };
+            lo = hi;
+        }
+        obs.pass(v);
+        width *= 2;
+        level += 1;
+    }
+}
+
+// merge the sorted runs v[..mid] and v[mid..]
+// unsafe because buf must have room for the shorter run
+pub(crate) unsafe fn merge_runs<T, F, O>(
+    v: &mut [T],
+    mid: usize,
+    off: usize,
+    buf: *mut T,
+    is_less: &mut F,
+    obs: &O,
+) where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    if mid == 0 || mid >= v.len() {
+        return;
+    }
+    // already in order, nothing to do, sorted input is linear
+    obs.compare(off + mid, off + mid - 1);
+    if !is_less(&v[mid], &v[mid - 1]) {
+        return;
+    }
+    if mid <= v.len() - mid {
+        merge_lo(v, mid, off, buf, is_less, obs);
+    } else {
+        merge_hi(v, mid, off, buf, is_less, obs);
+    }
+}
+
+// Elements sat in buf between start and end
+// belong in the gap of v starting at dest.
+// Dropping the hole puts them back, so if is_less panics
+// v still holds every element exactly once
+pub(crate) struct Hole<T> {
+    pub(crate) start: *mut T,
+    pub(crate) end: *mut T,
+    pub(crate) dest: *mut T,
+}
+
+impl<T> Drop for Hole<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let n = self.end.offset_from(self.start) as usize;
+            ptr::copy_nonoverlapping(self.start, self.dest, n);
+        }
+    }
+}
+
+// left run is shorter, copy it out and merge forwards
+unsafe fn merge_lo<T, F, O>(
+    v: &mut [T],
+    mid: usize,
+    off: usize,
+    buf: *mut T,
+    is_less: &mut F,
+    obs: &O,
+) where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    let len = v.len();
+    let v = v.as_mut_ptr();
+    ptr::copy_nonoverlapping(v, buf, mid);
+    let mut hole = Hole {
+        start: buf,
+        end: buf.add(mid),
+        dest: v,
+    };
+    let mut right = v.add(mid);
+    let v_end = v.add(len);
+
+    while hole.start < hole.end && right < v_end {
+        obs.compare(
+            off + right.offset_from(v) as usize,
+            off + hole.start.offset_from(buf) as usize,
+        );
+        // ties take the left, keeps the sort stable
+        if is_less(&*right, &*hole.start) {
+            ptr::copy_nonoverlapping(right, hole.dest, 1);
+            right = right.add(1);
+        } else {
+            ptr::copy_nonoverlapping(hole.start, hole.dest, 1);
+            hole.start = hole.start.add(1);
+        }
+        hole.dest = hole.dest.add(1);
+    }
+    // dropping hole moves what is left of the left run into place
+}
+
+// right run is shorter, copy it out and merge backwards
+unsafe fn merge_hi<T, F, O>(
+    v: &mut [T],
+    mid: usize,
+    off: usize,
+    buf: *mut T,
+    is_less: &mut F,
+    obs: &O,
+) where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    let len = v.len();
+    let v = v.as_mut_ptr();
+    ptr::copy_nonoverlapping(v.add(mid), buf, len - mid);
+    let mut hole = Hole {
+        start: buf,
+        end: buf.add(len - mid),
+        dest: v.add(mid),
+    };
+    let mut out = v.add(len);
+
+    while v < hole.dest && hole.start < hole.end {
+        let l = hole.dest.sub(1);
+        let r = hole.end.sub(1);
+        obs.compare(
+            off + mid + r.offset_from(buf) as usize,
+            off + l.offset_from(v) as usize,
+        );
+        out = out.sub(1);
+        // ties take the right, keeps the sort stable
+        if is_less(&*r, &*l) {
+            ptr::copy_nonoverlapping(l, out, 1);
+            hole.dest = l;
+        } else {
+            ptr::copy_nonoverlapping(r, out, 1);
+            hole.end = r;
+        }
+    }
+    // dropping hole moves what is left of the right run into place
+}
+
+// below this many elements merge_sort_rayon sorts and merges sequentially
+pub const MERGE_RAYON_THRESHOLD: usize = 4096;
+
+// Stable parallel merge sort
+// sorts each half with rayon::join then merges the halves in parallel too
+pub fn merge_sort_rayon<T: Send + Sync + PartialOrd>(v: &mut [T]) {
+    merge_sort_rayon_with_threshold(v, MERGE_RAYON_THRESHOLD);
+}
+
+// threshold is the size below which the work is not split any more
+pub fn merge_sort_rayon_with_threshold<T: Send + Sync + PartialOrd>(v: &mut [T], threshold: usize) {
+    merge_sort_rayon_impl(v, threshold, &|a: &T, b: &T| a < b);
+}
+
+pub fn merge_sort_rayon_by<T, F>(v: &mut [T], compare: F)
+where
+    T: Send + Sync,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    merge_sort_rayon_impl(v, MERGE_RAYON_THRESHOLD, &|a: &T, b: &T| {
+        compare(a, b) == Ordering::Less
+    });
+}
+
+pub fn merge_sort_rayon_by_key<T, K, F>(v: &mut [T], f: F)
+where
+    T: Send + Sync,
+    K: PartialOrd,
+    F: Fn(&T) -> K + Sync,
+{
+    merge_sort_rayon_impl(v, MERGE_RAYON_THRESHOLD, &|a: &T, b: &T| f(a) < f(b));
+}
+
+// raw pointer into the scratch buffer that may cross threads
+// every task is given its own part of the buffer
+struct SendPtr<T>(*mut T);
+unsafe impl<T> Send for SendPtr<T> {}
+unsafe impl<T> Sync for SendPtr<T> {}
+
+impl<T> Clone for SendPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for SendPtr<T> {}
+
+impl<T> SendPtr<T> {
+    fn add(self, n: usize) -> Self {
+        SendPtr(unsafe { self.0.add(n) })
+    }
+}
+
+pub(crate) fn merge_sort_rayon_impl<T, F>(v: &mut [T], threshold: usize, is_less: &F)
+where
+    T: Send + Sync,
+    F: Fn(&T, &T) -> bool + Sync,
+{
+    if std::mem::size_of::<T>() == 0 {
+        return;
+    }
+    // one buffer for the whole sort, n long so each merge can write all of its output
+    let mut scratch: Vec<T> = Vec::with_capacity(v.len());
+    // a threshold under 2 would never stop splitting
+    let threshold = std::cmp::max(threshold, 2);
+    merge_sort_rayon_at(v, SendPtr(scratch.as_mut_ptr()), threshold, is_less);
+}
+
+// buf is the part of the scratch buffer lined up with v
+fn merge_sort_rayon_at<T, F>(v: &mut [T], buf: SendPtr<T>, threshold: usize, is_less: &F)
+where
+    T: Send + Sync,
+    F: Fn(&T, &T) -> bool + Sync,
+{
+    let len = v.len();
+    if len <= threshold {
+        merge_sort_slice_at(v, 0, 0, buf.0, &mut |a: &T, b: &T| is_less(a, b), &NoOp);
+        return;
+    }
+
+    let mid = len / 2;
+    let (a, b) = v.split_at_mut(mid);
+    rayon::join(
+        || merge_sort_rayon_at(a, buf, threshold, is_less),
+        || merge_sort_rayon_at(b, buf.add(mid), threshold, is_less),
+    );
+    if !is_less(&v[mid], &v[mid - 1]) {
+        return;
+    }
+
+    // v is only read while merging, so a panic in is_less leaves it whole
+    // and the bits copied into buf are just forgotten
+    let (a, b) = v.split_at(mid);
+    par_merge(a, b, buf, threshold, is_less);
+    unsafe {
+        ptr::copy_nonoverlapping(buf.0, v.as_mut_ptr(), len);
+    }
+}
+
+// merge a and b into dest, a is before b for stability.
+// The larger run is split at its middle and the other is binary searched
+// for the same point, giving two smaller merges to run in parallel
+fn par_merge<T, F>(a: &[T], b: &[T], dest: SendPtr<T>, threshold: usize, is_less: &F)
+where
+    T: Send + Sync,
+    F: Fn(&T, &T) -> bool + Sync,
+{
+    if a.len() + b.len() <= threshold {
+        unsafe { seq_merge(a, b, dest.0, is_less) };
+        return;
+    }
+
+    let (am, bm) = if a.len() >= b.len() {
+        // b's that are less than a[am] go before it, equal ones after
+        let am = a.len() / 2;
+        (am, lower_bound(b, &a[am], is_less))
+    } else {
+        // a's that are not greater than b[bm] go before it
+        let bm = b.len() / 2;
+        (upper_bound(a, &b[bm], is_less), bm)
+    };
+
+    let (a_lo, a_hi) = a.split_at(am);
+    let (b_lo, b_hi) = b.split_at(bm);
+    rayon::join(
+        || par_merge(a_lo, b_lo, dest, threshold, is_less),
+        || par_merge(a_hi, b_hi, dest.add(am + bm), threshold, is_less),
+    );
+}
+
+// first index in v where !(v[i] < x)
+fn lower_bound<T, F: Fn(&T, &T) -> bool>(v: &[T], x: &T, is_less: &F) -> usize {
+    let (mut lo, mut hi) = (0, v.len());
+    while lo < hi {
+        let m = lo + (hi - lo) / 2;
+        if is_less(&v[m], x) {
+            lo = m + 1;
+        } else {
+            hi = m;
+        }
+    }
+    lo
+}
+
+// first index in v where x < v[i]
+fn upper_bound<T, F: Fn(&T, &T) -> bool>(v: &[T], x: &T, is_less: &F) -> usize {
+    let (mut lo, mut hi) = (0, v.len());
+    while lo < hi {
+        let m = lo + (hi - lo) / 2;
+        if is_less(x, &v[m]) {
+            hi = m;
+        } else {
+            lo = m + 1;
+        }
+    }
+    lo
+}
+
+// bitwise copies a and b merged into dest, which needs room for both
+unsafe fn seq_merge<T, F: Fn(&T, &T) -> bool>(a: &[T], b: &[T], dest: *mut T, is_less: &F) {
+    let (mut i, mut j, mut out) = (0, 0, dest);
+    while i < a.len() && j < b.len() {
+        // ties take a, keeps the sort stable
+        if is_less(&b[j], &a[i]) {
+            ptr::copy_nonoverlapping(&b[j], out, 1);
+            j += 1;
+        } else {
+            ptr::copy_nonoverlapping(&a[i], out, 1);
+            i += 1;
+        }
+        out = out.add(1);
+    }
+    ptr::copy_nonoverlapping(a[i..].as_ptr(), out, a.len() - i);
+    out = out.add(a.len() - i);
+    ptr::copy_nonoverlapping(b[j..].as_ptr(), out, b.len() - j);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::b_rand::RandGen;
+    use crate::observer::Counter;
+
+    fn random_vec(seed: usize, n: usize, max: usize) -> Vec<usize> {
+        let mut r = RandGen::new(seed);
+        (0..n).map(|_| r.next_v(max)).collect()
+    }
+
+    #[test]
+    fn test_merge_sort_slice() {
+        // cargo test test_merge_sort_slice -- --nocapture
+        let mut v = vec![4, 6, 1, 8, 11, 13, 3];
+        merge_sort_slice(&mut v);
+
+        let sorted_v = vec![1, 3, 4, 6, 8, 11, 13];
+        assert_eq!(v, sorted_v);
+
+        let mut v = vec![4, 6, 1, 8, 11, 13, 3];
+        merge_sort_bottom_up(&mut v);
+        assert_eq!(v, sorted_v);
+    }
+    #[test]
+    fn test_merge_sort_slice_random() {
+        // cargo test test_merge_sort_slice_random -- --nocapture
+        let mut buf = Vec::new();
+        for n in 0..300 {
+            let v = random_vec(n + 1, n, 50);
+            let mut sorted_v = v.clone();
+            sorted_v.sort();
+
+            let mut a = v.clone();
+            merge_sort_slice_with_buf(&mut a, &mut buf);
+            assert_eq!(a, sorted_v);
+
+            let mut b = v.clone();
+            merge_sort_bottom_up_with_buf(&mut b, &mut buf);
+            assert_eq!(b, sorted_v);
+        }
+        // one reserve for the largest, no growth after
+        assert!(buf.capacity() >= 299 / 2);
+        assert!(buf.is_empty());
+    }
+    #[test]
+    fn test_merge_sort_slice_stable() {
+        // cargo test test_merge_sort_slice_stable -- --nocapture
+        // tag each key with its position, equal keys must keep tag order
+        let keys = random_vec(77, 1000, 10);
+        let tagged: Vec<(usize, usize)> =
+            keys.into_iter().enumerate().map(|(i, k)| (k, i)).collect();
+
+        let mut a = tagged.clone();
+        merge_sort_slice_by_key(&mut a, |p| p.0);
+        let mut b = tagged.clone();
+        merge_sort_bottom_up_by(&mut b, |x, y| x.0.cmp(&y.0));
+
+        let mut sorted_v = tagged;
+        sorted_v.sort();
+        assert_eq!(a, sorted_v);
+        assert_eq!(b, sorted_v);
+    }
+    #[test]
+    fn test_merge_sort_slice_by() {
+        // cargo test test_merge_sort_slice_by -- --nocapture
+        let mut v: Vec<String> = vec!["pete", "andy", "james", "dave"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        merge_sort_slice_by(&mut v, |a, b| b.cmp(a));
+        assert_eq!(v, vec!["pete", "james", "dave", "andy"]);
+    }
+    #[test]
+    fn test_merge_sort_slice_sorted_is_linear() {
+        // cargo test test_merge_sort_slice_sorted_is_linear -- --nocapture
+        let mut v: Vec<u32> = (0..1024).collect();
+        let c = Counter::new();
+        merge_sort_slice_observed(&mut v, &c);
+        assert!(c.totals().comparisons < 1024);
+
+        let c = Counter::new();
+        merge_sort_bottom_up_observed(&mut v, &c);
+        assert!(c.totals().comparisons < 1024);
+    }
+    #[test]
+    fn test_merge_sort_slice_panic_safe() {
+        // cargo test test_merge_sort_slice_panic_safe -- --nocapture
+        // a panicking comparator must leave every element in v once
+        let v: Vec<String> = random_vec(5, 200, 1000)
+            .into_iter()
+            .map(|x| x.to_string())
+            .collect();
+        for stop in &[10, 300, 900] {
+            let mut a = v.clone();
+            let mut n = 0;
+            let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                merge_sort_slice_by(&mut a, |x, y| {
+                    n += 1;
+                    if n == *stop {
+                        panic!("stop");
+                    }
+                    x.cmp(y)
+                });
+            }));
+            assert!(res.is_err());
+
+            let mut a_sorted = a.clone();
+            a_sorted.sort();
+            let mut v_sorted = v.clone();
+            v_sorted.sort();
+            assert_eq!(a_sorted, v_sorted);
+        }
+    }
+
+    #[test]
+    fn test_merge_sort_rayon() {
+        // cargo test test_merge_sort_rayon -- --nocapture
+        let mut v = vec![4, 6, 1, 8, 11, 13, 3];
+        merge_sort_rayon(&mut v);
+
+        let sorted_v = vec![1, 3, 4, 6, 8, 11, 13];
+        assert_eq!(v, sorted_v);
+    }
+    #[test]
+    fn test_merge_sort_rayon_thresholds() {
+        // cargo test test_merge_sort_rayon_thresholds -- --nocapture
+        let v = random_vec(21, 20_000, 5_000);
+        let mut sorted_v = v.clone();
+        sorted_v.sort();
+        for t in &[0, 1, 2, 3, 64, 4096, 100_000] {
+            let mut a = v.clone();
+            merge_sort_rayon_with_threshold(&mut a, *t);
+            assert_eq!(a, sorted_v);
+        }
+    }
+    #[test]
+    fn test_merge_sort_rayon_stable() {
+        // cargo test test_merge_sort_rayon_stable -- --nocapture
+        let keys = random_vec(33, 50_000, 20);
+        let tagged: Vec<(usize, usize)> =
+            keys.into_iter().enumerate().map(|(i, k)| (k, i)).collect();
+
+        let mut a = tagged.clone();
+        merge_sort_rayon_by_key(&mut a, |p| p.0);
+        let mut b = tagged.clone();
+        merge_sort_rayon_by(&mut b, |x, y| y.0.cmp(&x.0));
+
+        let mut sorted_v = tagged;
+        sorted_v.sort();
+        assert_eq!(a, sorted_v);
+
+        // descending keeps tag order within equal keys too
+        sorted_v.sort_by_key(|p| std::cmp::Reverse(p.0));
+        assert_eq!(b, sorted_v);
+    }
+    #[test]
+    fn test_merge_sort_rayon_strings() {
+        // cargo test test_merge_sort_rayon_strings -- --nocapture
+        // owned values are moved not cloned, nothing is dropped twice
+        let v: Vec<String> = random_vec(8, 10_000, 100_000)
+            .into_iter()
+            .map(|x| x.to_string())
+            .collect();
+        let mut sorted_v = v.clone();
+        sorted_v.sort();
+
+        let mut a = v;
+        merge_sort_rayon_with_threshold(&mut a, 100);
+        assert_eq!(a, sorted_v);
+    }
+}
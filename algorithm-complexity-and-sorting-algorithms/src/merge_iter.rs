@@ -0,0 +1,438 @@
+use std::cmp::Ordering;
+
+use crate::heap::sift_down_at;
+use crate::observer::NoOp;
+
+// Lazily merges sorted iterators into one sorted iterator.
+// A min heap holds the next item from each input, so each item
+// costs O(ln(k)) for k inputs. Stable, equal items come out
+// in input order, so everything from iters[0] before iters[1].
+// Works on endless inputs, nothing is read until it is needed
+pub fn merge_sorted<I, J, T>(iters: I) -> MergeSorted<J::IntoIter, impl FnMut(&T, &T) -> bool>
+where
+    I: IntoIterator<Item = J>,
+    J: IntoIterator<Item = T>,
+    T: PartialOrd,
+{
+    MergeSorted::new(iters, |a: &T, b: &T| a < b)
+}
+
+pub fn merge_sorted_by<I, J, T, F>(
+    iters: I,
+    mut compare: F,
+) -> MergeSorted<J::IntoIter, impl FnMut(&T, &T) -> bool>
+where
+    I: IntoIterator<Item = J>,
+    J: IntoIterator<Item = T>,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    MergeSorted::new(iters, move |a: &T, b: &T| compare(a, b) == Ordering::Less)
+}
+
+pub fn merge_sorted_by_key<I, J, T, K, F>(
+    iters: I,
+    mut f: F,
+) -> MergeSorted<J::IntoIter, impl FnMut(&T, &T) -> bool>
+where
+    I: IntoIterator<Item = J>,
+    J: IntoIterator<Item = T>,
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    MergeSorted::new(iters, move |a: &T, b: &T| f(a) < f(b))
+}
+
+pub struct MergeSorted<I: Iterator, F> {
+    iters: Vec<I>,
+    // (next item, which input it came from)
+    heap: Vec<(I::Item, usize)>,
+    is_less: F,
+    dedup: bool,
+}
+
+impl<I, F> MergeSorted<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> bool,
+{
+    fn new<J: IntoIterator<IntoIter = I>, II: IntoIterator<Item = J>>(
+        iters: II,
+        is_less: F,
+    ) -> Self {
+        let mut iters: Vec<I> = iters.into_iter().map(|j| j.into_iter()).collect();
+        let heap = iters
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, it)| it.next().map(|t| (t, i)))
+            .collect();
+        let mut m = MergeSorted {
+            iters,
+            heap,
+            is_less,
+            dedup: false,
+        };
+        for i in (0..m.heap.len() / 2).rev() {
+            m.sift_down(i);
+        }
+        m
+    }
+
+    // only the first of each run of equal items, across all the inputs
+    // as well as within one
+    pub fn dedup(mut self) -> Self {
+        self.dedup = true;
+        self
+    }
+
+    // Merge join, groups the equal items from every input together.
+    // Each group is (input, item) in input order,
+    // a group with an item from every input is an inner join match
+    pub fn join(self) -> MergeJoin<I, F> {
+        MergeJoin(self)
+    }
+
+    // the heap is a max heap on "comes out later",
+    // the smaller item or on a tie the earlier input is on top
+    fn sift_down(&mut self, i: usize) {
+        let is_less = &mut self.is_less;
+        let len = self.heap.len();
+        sift_down_at(
+            &mut self.heap,
+            i,
+            len,
+            0,
+            &mut |x: &(I::Item, usize), y: &(I::Item, usize)| {
+                is_less(&y.0, &x.0) || (!is_less(&x.0, &y.0) && y.1 < x.1)
+            },
+            &NoOp,
+        );
+    }
+
+    // take the top and refill from the input it came from
+    fn pop(&mut self) -> Option<(I::Item, usize)> {
+        let i = self.heap.first()?.1;
+        let t = match self.iters[i].next() {
+            Some(next) => std::mem::replace(&mut self.heap[0].0, next),
+            None => self.heap.swap_remove(0).0,
+        };
+        self.sift_down(0);
+        Some((t, i))
+    }
+
+    fn top_equals(&mut self, t: &I::Item) -> bool {
+        match self.heap.first() {
+            Some(top) => equal(&mut self.is_less, &top.0, t),
+            None => false,
+        }
+    }
+}
+
+impl<I, F> Iterator for MergeSorted<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> bool,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let (t, _) = self.pop()?;
+        if self.dedup {
+            while self.top_equals(&t) {
+                self.pop();
+            }
+        }
+        Some(t)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let mut lo = self.heap.len();
+        let mut hi = Some(self.heap.len());
+        for it in &self.iters {
+            let (l, h) = it.size_hint();
+            lo = lo.saturating_add(l);
+            hi = match (hi, h) {
+                (Some(a), Some(b)) => a.checked_add(b),
+                _ => None,
+            };
+        }
+        if self.dedup {
+            lo = lo.min(1);
+        }
+        (lo, hi)
+    }
+}
+
+pub struct MergeJoin<I: Iterator, F>(MergeSorted<I, F>);
+
+impl<I, F> Iterator for MergeJoin<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> bool,
+{
+    type Item = Vec<(usize, I::Item)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (t, i) = self.0.pop()?;
+        let mut group = vec![(i, t)];
+        while self.0.top_equals(&group[0].1) {
+            let (t, i) = self.0.pop().unwrap();
+            group.push((i, t));
+        }
+        Some(group)
+    }
+}
+
+// The 2 input case, no heap, one compare per item.
+// Stable, a is taken unless b is strictly less
+pub fn merge_two<A, B, T>(
+    a: A,
+    b: B,
+) -> MergeTwo<A::IntoIter, B::IntoIter, impl FnMut(&T, &T) -> bool>
+where
+    A: IntoIterator<Item = T>,
+    B: IntoIterator<Item = T>,
+    T: PartialOrd,
+{
+    MergeTwo::new(a.into_iter(), b.into_iter(), |a: &T, b: &T| a < b)
+}
+
+pub fn merge_two_by<A, B, T, F>(
+    a: A,
+    b: B,
+    mut compare: F,
+) -> MergeTwo<A::IntoIter, B::IntoIter, impl FnMut(&T, &T) -> bool>
+where
+    A: IntoIterator<Item = T>,
+    B: IntoIterator<Item = T>,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    MergeTwo::new(a.into_iter(), b.into_iter(), move |a: &T, b: &T| {
+        compare(a, b) == Ordering::Less
+    })
+}
+
+pub fn merge_two_by_key<A, B, T, K, F>(
+    a: A,
+    b: B,
+    mut f: F,
+) -> MergeTwo<A::IntoIter, B::IntoIter, impl FnMut(&T, &T) -> bool>
+where
+    A: IntoIterator<Item = T>,
+    B: IntoIterator<Item = T>,
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    MergeTwo::new(a.into_iter(), b.into_iter(), move |a: &T, b: &T| {
+        f(a) < f(b)
+    })
+}
+
+pub struct MergeTwo<A: Iterator, B, F> {
+    a: A,
+    b: B,
+    a_peek: Option<A::Item>,
+    b_peek: Option<A::Item>,
+    // how many have been taken from each side
+    a_n: usize,
+    b_n: usize,
+    is_less: F,
+    dedup: bool,
+}
+
+impl<A, B, F> MergeTwo<A, B, F>
+where
+    A: Iterator,
+    B: Iterator<Item = A::Item>,
+    F: FnMut(&A::Item, &A::Item) -> bool,
+{
+    pub(crate) fn new(mut a: A, mut b: B, is_less: F) -> Self {
+        MergeTwo {
+            a_peek: a.next(),
+            b_peek: b.next(),
+            a,
+            b,
+            a_n: 0,
+            b_n: 0,
+            is_less,
+            dedup: false,
+        }
+    }
+
+    // only the first of each run of equal items
+    pub fn dedup(mut self) -> Self {
+        self.dedup = true;
+        self
+    }
+
+    // compared(a_n, b_n) is called before each compare with how many
+    // items have been taken from each side, merge_sort reports those
+    pub(crate) fn next_at<C: FnMut(usize, usize)>(&mut self, compared: &mut C) -> Option<A::Item> {
+        let take_b = match (&self.a_peek, &self.b_peek) {
+            (Some(a_val), Some(b_val)) => {
+                compared(self.a_n, self.b_n);
+                (self.is_less)(b_val, a_val)
+            }
+            (Some(_), None) => false,
+            (None, Some(_)) => true,
+            (None, None) => return None,
+        };
+        let t = if take_b {
+            self.b_n += 1;
+            std::mem::replace(&mut self.b_peek, self.b.next())
+        } else {
+            self.a_n += 1;
+            std::mem::replace(&mut self.a_peek, self.a.next())
+        }
+        .unwrap();
+
+        if self.dedup {
+            while matches!(&self.a_peek, Some(x) if equal(&mut self.is_less, x, &t)) {
+                self.a_peek = self.a.next();
+            }
+            while matches!(&self.b_peek, Some(x) if equal(&mut self.is_less, x, &t)) {
+                self.b_peek = self.b.next();
+            }
+        }
+        Some(t)
+    }
+}
+
+impl<A, B, F> Iterator for MergeTwo<A, B, F>
+where
+    A: Iterator,
+    B: Iterator<Item = A::Item>,
+    F: FnMut(&A::Item, &A::Item) -> bool,
+{
+    type Item = A::Item;
+
+    fn next(&mut self) -> Option<A::Item> {
+        self.next_at(&mut |_, _| {})
+    }
+}
+
+fn equal<T, F: FnMut(&T, &T) -> bool>(is_less: &mut F, a: &T, b: &T) -> bool {
+    !is_less(a, b) && !is_less(b, a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::b_rand::XorShift;
+
+    fn sorted_runs(seed: u64, k: usize) -> Vec<Vec<u32>> {
+        let mut r = XorShift::new(seed);
+        (0..k)
+            .map(|_| {
+                let n = (r.next_u64() % 200) as usize;
+                let mut v: Vec<u32> = (0..n).map(|_| (r.next_u64() % 100) as u32).collect();
+                v.sort();
+                v
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_sorted() {
+        // cargo test test_merge_sorted -- --nocapture
+        for k in [0, 1, 2, 3, 10, 33].iter().cloned() {
+            let runs = sorted_runs(k as u64 + 1, k);
+            let mut sorted_v: Vec<u32> = runs.iter().flatten().cloned().collect();
+            sorted_v.sort();
+
+            let m = merge_sorted(runs.clone());
+            assert_eq!(m.size_hint(), (sorted_v.len(), Some(sorted_v.len())));
+            assert_eq!(m.collect::<Vec<_>>(), sorted_v);
+
+            let mut dedup_v = sorted_v.clone();
+            dedup_v.dedup();
+            assert_eq!(merge_sorted(runs).dedup().collect::<Vec<_>>(), dedup_v);
+        }
+    }
+    #[test]
+    fn test_merge_sorted_stable() {
+        // cargo test test_merge_sorted_stable -- --nocapture
+        // (key, input, position in input)
+        let runs: Vec<Vec<(u32, usize, usize)>> = sorted_runs(5, 6)
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| v.into_iter().enumerate().map(|(j, x)| (x, i, j)).collect())
+            .collect();
+        let res: Vec<_> = merge_sorted_by_key(runs, |t| t.0).collect();
+        assert!(res.windows(2).all(|w| w[0] <= w[1]));
+
+        let res: Vec<_> = merge_sorted_by(
+            vec![vec![(3, 'a'), (1, 'b')], vec![(3, 'c'), (2, 'd'), (1, 'e')]],
+            |a: &(u32, char), b: &(u32, char)| b.0.cmp(&a.0),
+        )
+        .collect();
+        assert_eq!(res, vec![(3, 'a'), (3, 'c'), (2, 'd'), (1, 'b'), (1, 'e')]);
+    }
+    #[test]
+    fn test_merge_sorted_lazy() {
+        // cargo test test_merge_sorted_lazy -- --nocapture
+        // endless inputs, only what is taken gets read
+        let res: Vec<u64> = merge_sorted(vec![(0..).step_by(3), (0..).step_by(5)])
+            .dedup()
+            .take(8)
+            .collect();
+        assert_eq!(res, vec![0, 3, 5, 6, 9, 10, 12, 15]);
+
+        let res: Vec<u64> = merge_two((0..).step_by(2), (1..).step_by(2))
+            .take(5)
+            .collect();
+        assert_eq!(res, vec![0, 1, 2, 3, 4]);
+    }
+    #[test]
+    fn test_merge_join() {
+        // cargo test test_merge_join -- --nocapture
+        let users = vec![(1, "ann"), (2, "bob"), (4, "cat")];
+        let orders = vec![(1, "tea"), (1, "jam"), (3, "pen"), (4, "cup")];
+        let groups: Vec<Vec<(usize, (u32, &str))>> =
+            merge_sorted_by_key(vec![users, orders], |p| p.0)
+                .join()
+                .collect();
+        assert_eq!(groups.len(), 4);
+        assert_eq!(
+            groups[0],
+            vec![(0, (1, "ann")), (1, (1, "tea")), (1, (1, "jam"))]
+        );
+        assert_eq!(groups[1], vec![(0, (2, "bob"))]);
+
+        // inner join, keys found in both
+        let both: Vec<u32> = groups
+            .iter()
+            .filter(|g| g.iter().any(|p| p.0 == 0) && g.iter().any(|p| p.0 == 1))
+            .map(|g| (g[0].1).0)
+            .collect();
+        assert_eq!(both, vec![1, 4]);
+    }
+    #[test]
+    fn test_merge_two() {
+        // cargo test test_merge_two -- --nocapture
+        let runs = sorted_runs(9, 2);
+        let mut sorted_v: Vec<u32> = runs.iter().flatten().cloned().collect();
+        sorted_v.sort();
+        let res: Vec<u32> = merge_two(runs[0].clone(), runs[1].clone()).collect();
+        assert_eq!(res, sorted_v);
+
+        sorted_v.dedup();
+        let res: Vec<u32> = merge_two(runs[0].clone(), runs[1].clone())
+            .dedup()
+            .collect();
+        assert_eq!(res, sorted_v);
+
+        let a = vec![(1, 'a'), (2, 'b'), (2, 'c')];
+        let b = vec![(0, 'x'), (2, 'y'), (3, 'z')];
+        let res: Vec<char> = merge_two_by_key(a.clone(), b.clone(), |p| p.0)
+            .map(|p| p.1)
+            .collect();
+        assert_eq!(res, vec!['x', 'a', 'b', 'c', 'y', 'z']);
+
+        let res: Vec<char> = merge_two_by(a.into_iter().rev(), b.into_iter().rev(), |x, y| {
+            y.0.cmp(&x.0)
+        })
+        .map(|p| p.1)
+        .collect();
+        assert_eq!(res, vec!['z', 'c', 'b', 'y', 'a', 'x']);
+    }
+}
@@ -0,0 +1,265 @@
+use std::fmt::{self, Debug};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// Hooks called by the sorts as they run
+// every method has an empty default so an observer
+// only implements the events it cares about.
+// Indices are positions in the slice given to the top level call.
+// Methods take &self so one observer can be shared by the threaded sorts
+pub trait SortObserver<T> {
+    // two elements are about to be compared
+    fn compare(&self, _a: usize, _b: usize) {}
+
+    // two elements are about to be swapped
+    fn swap(&self, _a: usize, _b: usize) {}
+
+    // the range lo..hi has been split around the pivot now at p
+    fn partition(&self, _lo: usize, _hi: usize, _p: usize) {}
+
+    // a recursive call has started, the top level is depth 0
+    fn depth(&self, _d: usize) {}
+
+    // the slice being worked on after a step
+    fn pass(&self, _v: &[T]) {}
+}
+
+// so a borrowed or dyn observer can be passed on as it is
+impl<T, O: SortObserver<T> + ?Sized> SortObserver<T> for &O {
+    fn compare(&self, a: usize, b: usize) {
+        (**self).compare(a, b);
+    }
+
+    fn swap(&self, a: usize, b: usize) {
+        (**self).swap(a, b);
+    }
+
+    fn partition(&self, lo: usize, hi: usize, p: usize) {
+        (**self).partition(lo, hi, p);
+    }
+
+    fn depth(&self, d: usize) {
+        (**self).depth(d);
+    }
+
+    fn pass(&self, v: &[T]) {
+        (**self).pass(v);
+    }
+}
+
+// Default observer, does nothing and costs nothing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoOp;
+
+impl<T> SortObserver<T> for NoOp {}
+
+// Prints the working slice after every step
+// this is the old println tracing, so it needs Debug
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tracer;
+
+impl<T: Debug> SortObserver<T> for Tracer {
+    fn swap(&self, a: usize, b: usize) {
+        println!("swap:{},{}", a, b);
+    }
+
+    fn partition(&self, lo: usize, hi: usize, p: usize) {
+        println!("partition:{}..{} at {}", lo, hi, p);
+    }
+
+    fn pass(&self, v: &[T]) {
+        println!("{:?}", v);
+    }
+}
+
+// Totals from a Counter, compare these against
+// the n^2 or n ln(n) expected for the input size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Counts {
+    pub comparisons: usize,
+    pub swaps: usize,
+    pub partitions: usize,
+    pub max_depth: usize,
+}
+
+impl fmt::Display for Counts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "comparisons:{} swaps:{} partitions:{} max_depth:{}",
+            self.comparisons, self.swaps, self.partitions, self.max_depth
+        )
+    }
+}
+
+// Counts every event, atomics so the parallel sorts can share it
+#[derive(Debug, Default)]
+pub struct Counter {
+    comparisons: AtomicUsize,
+    swaps: AtomicUsize,
+    partitions: AtomicUsize,
+    max_depth: AtomicUsize,
+}
+
+impl Counter {
+    pub fn new() -> Self {
+        Counter::default()
+    }
+
+    pub fn totals(&self) -> Counts {
+        Counts {
+            comparisons: self.comparisons.load(Ordering::Relaxed),
+            swaps: self.swaps.load(Ordering::Relaxed),
+            partitions: self.partitions.load(Ordering::Relaxed),
+            max_depth: self.max_depth.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn reset(&self) {
+        self.comparisons.store(0, Ordering::Relaxed);
+        self.swaps.store(0, Ordering::Relaxed);
+        self.partitions.store(0, Ordering::Relaxed);
+        self.max_depth.store(0, Ordering::Relaxed);
+    }
+}
+
+impl<T> SortObserver<T> for Counter {
+    fn compare(&self, _a: usize, _b: usize) {
+        self.comparisons.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn swap(&self, _a: usize, _b: usize) {
+        self.swaps.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn partition(&self, _lo: usize, _hi: usize, _p: usize) {
+        self.partitions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn depth(&self, d: usize) {
+        self.max_depth.fetch_max(d, Ordering::Relaxed);
+    }
+}
+
+// One event from a sort, in the order it happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Compare(usize, usize),
+    Swap(usize, usize),
+    Partition { lo: usize, hi: usize, p: usize },
+    Depth(usize),
+    // a step finished
+    Pass,
+}
+
+// Keeps every event so a sort can be played back afterwards.
+// Replaying the swaps on a copy of the input redoes the sort
+// for the in place sorts, the ones that only move items by swapping.
+// The parallel sorts share it through the Mutex, their events interleave
+#[derive(Debug, Default)]
+pub struct Recorder {
+    events: Mutex<Vec<Event>>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Recorder::default()
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        self.events.lock().unwrap().clone()
+    }
+
+    pub fn into_events(self) -> Vec<Event> {
+        self.events.into_inner().unwrap()
+    }
+
+    fn push(&self, e: Event) {
+        self.events.lock().unwrap().push(e);
+    }
+}
+
+impl<T> SortObserver<T> for Recorder {
+    fn compare(&self, a: usize, b: usize) {
+        self.push(Event::Compare(a, b));
+    }
+
+    fn swap(&self, a: usize, b: usize) {
+        self.push(Event::Swap(a, b));
+    }
+
+    fn partition(&self, lo: usize, hi: usize, p: usize) {
+        self.push(Event::Partition { lo, hi, p });
+    }
+
+    fn depth(&self, d: usize) {
+        self.push(Event::Depth(d));
+    }
+
+    fn pass(&self, _v: &[T]) {
+        self.push(Event::Pass);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_counter_totals() {
+        // cargo test test_counter_totals -- --nocapture
+        let c = Counter::new();
+        let obs: &dyn SortObserver<i32> = &c;
+        obs.compare(0, 1);
+        obs.compare(1, 2);
+        obs.swap(0, 1);
+        obs.partition(0, 3, 1);
+        obs.depth(4);
+        obs.depth(2);
+
+        let t = c.totals();
+        assert_eq!(
+            t,
+            Counts {
+                comparisons: 2,
+                swaps: 1,
+                partitions: 1,
+                max_depth: 4,
+            }
+        );
+        println!("{}", t);
+
+        c.reset();
+        assert_eq!(c.totals(), Counts::default());
+    }
+    #[test]
+    fn test_recorder_replay() {
+        // cargo test test_recorder_replay -- --nocapture
+        let v: Vec<u32> = (0..200).map(|i| (i * 7_919) % 61).collect();
+        let mut sorted_v = v.clone();
+        sorted_v.sort();
+        for s in crate::sorter::sorters::<u32>() {
+            let r = Recorder::new();
+            let mut a = v.clone();
+            s.sort_observed(&mut a, &r);
+            let mut b = v.clone();
+            for e in r.into_events() {
+                if let Event::Swap(x, y) = e {
+                    b.swap(x, y);
+                }
+            }
+            // the in place sorts only move items by swapping
+            let info = s.info();
+            if info.in_place && info.observed {
+                assert_eq!(b, sorted_v, "{}", info.name);
+            }
+        }
+        let r = Recorder::new();
+        crate::quick_sort_observed(&mut [3, 1, 2], &r);
+        let events = r.into_events();
+        assert_eq!(events[0], Event::Depth(0));
+        assert!(events.contains(&Event::Pass));
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, Event::Partition { lo: 0, hi: 3, .. })));
+    }
+}
@@ -0,0 +1,475 @@
+use crate::introsort::insertion_sort_at;
+use crate::observer::NoOp;
+use std::convert::TryFrom;
+
+// MSD buckets this small are finished with insertion sort
+const MSD_CUTOFF: usize = 16;
+
+// counting sort needs a bucket for every key from min to max,
+// past this many a key (plus one byte's worth) LSD radix sort does it instead
+const COUNTING_SPREAD: usize = 8;
+
+// Integers that can be sorted a byte at a time.
+// to_radix must keep the order, so signed types flip their sign bit
+// and the most negative value becomes 0
+pub trait RadixKey: Copy {
+    // bytes that can differ, LSD radix sort does one pass per byte
+    const BYTES: usize;
+
+    fn to_radix(self) -> u64;
+}
+
+macro_rules! radix_unsigned {
+    ($($t:ty),*) => {$(
+        impl RadixKey for $t {
+            const BYTES: usize = std::mem::size_of::<$t>();
+
+            fn to_radix(self) -> u64 {
+                self as u64
+            }
+        }
+    )*};
+}
+
+macro_rules! radix_signed {
+    ($($t:ty => $u:ty),*) => {$(
+        impl RadixKey for $t {
+            const BYTES: usize = std::mem::size_of::<$t>();
+
+            fn to_radix(self) -> u64 {
+                ((self as $u) ^ (1 << (<$u>::BITS - 1))) as u64
+            }
+        }
+    )*};
+}
+
+radix_unsigned!(u8, u16, u32, u64, usize);
+radix_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, isize => usize);
+
+// LSD radix sort, O(n * BYTES), stable
+// one counting pass per byte from the lowest up,
+// a pass where every value has the same byte is skipped
+pub fn radix_sort<T: RadixKey>(v: &mut [T]) {
+    lsd(v, T::BYTES, |t| t.to_radix());
+}
+
+// Sort records by an integer field, stable.
+// The keys are sorted with their positions then the records
+// are moved into that order with swaps, so T need not be Copy
+pub fn radix_sort_by_key<T, K, F>(v: &mut [T], mut f: F)
+where
+    K: RadixKey,
+    F: FnMut(&T) -> K,
+{
+    let keys: Vec<u64> = v.iter().map(|t| f(t).to_radix()).collect();
+    apply_order(v, lsd_order(keys, K::BYTES));
+}
+
+// where each key belongs, the keys sorted along with their positions
+fn lsd_order(keys: Vec<u64>, bytes: usize) -> Vec<usize> {
+    let mut keyed: Vec<(u64, usize)> = keys.into_iter().zip(0..).collect();
+    lsd(&mut keyed, bytes, |p| p.0);
+    keyed.into_iter().map(|p| p.1).collect()
+}
+
+fn lsd<T: Copy, F: Fn(&T) -> u64>(v: &mut [T], bytes: usize, key: F) {
+    let n = v.len();
+    if n < 2 {
+        return;
+    }
+    let mut buf = v.to_vec();
+    for pass in 0..bytes {
+        let shift = pass * 8;
+        let mut counts = [0usize; 256];
+        for t in v.iter() {
+            counts[((key(t) >> shift) & 0xff) as usize] += 1;
+        }
+        if counts.contains(&n) {
+            continue;
+        }
+        // counts become where each bucket starts
+        let mut total = 0;
+        for c in counts.iter_mut() {
+            let here = *c;
+            *c = total;
+            total += here;
+        }
+        for t in v.iter() {
+            let b = ((key(t) >> shift) & 0xff) as usize;
+            buf[counts[b]] = *t;
+            counts[b] += 1;
+        }
+        v.copy_from_slice(&buf);
+    }
+}
+
+// order[i] is the index of the item that belongs at i
+// follow each cycle of the permutation swapping as we go
+fn apply_order<T>(v: &mut [T], mut order: Vec<usize>) {
+    for i in 0..v.len() {
+        if order[i] == usize::MAX {
+            continue;
+        }
+        let mut j = i;
+        loop {
+            let k = order[j];
+            order[j] = usize::MAX;
+            if k == i {
+                break;
+            }
+            v.swap(j, k);
+            j = k;
+        }
+    }
+}
+
+// Counting sort, O(n + range) where range is max - min
+// only worth it when the keys sit close together,
+// keys spread wider than that are radix sorted instead
+pub fn counting_sort<T: RadixKey>(v: &mut [T]) {
+    let (lo, range) = match key_range(v.iter().map(|t| t.to_radix())) {
+        Some((lo, hi)) => match counting_range(lo, hi, v.len()) {
+            Some(range) => (lo, range),
+            None => return radix_sort(v),
+        },
+        None => return,
+    };
+    // equal keys are equal values, so one copy of each is enough
+    let mut counts = vec![0usize; range];
+    let mut seen: Vec<Option<T>> = vec![None; range];
+    for t in v.iter() {
+        let b = (t.to_radix() - lo) as usize;
+        counts[b] += 1;
+        seen[b] = Some(*t);
+    }
+    let mut out = 0;
+    for (c, t) in counts.into_iter().zip(seen) {
+        if let Some(t) = t {
+            for x in &mut v[out..out + c] {
+                *x = t;
+            }
+            out += c;
+        }
+    }
+}
+
+// Stable counting sort of records by a small integer field
+pub fn counting_sort_by_key<T, K, F>(v: &mut [T], mut f: F)
+where
+    K: RadixKey,
+    F: FnMut(&T) -> K,
+{
+    let keys: Vec<u64> = v.iter().map(|t| f(t).to_radix()).collect();
+    let (lo, range) = match key_range(keys.iter().cloned()) {
+        Some((lo, hi)) => match counting_range(lo, hi, keys.len()) {
+            Some(range) => (lo, range),
+            None => return apply_order(v, lsd_order(keys, K::BYTES)),
+        },
+        None => return,
+    };
+    let mut starts = vec![0usize; range];
+    for k in &keys {
+        starts[(k - lo) as usize] += 1;
+    }
+    let mut total = 0;
+    for c in starts.iter_mut() {
+        let here = *c;
+        *c = total;
+        total += here;
+    }
+    let mut order = vec![0; keys.len()];
+    for (i, k) in keys.iter().enumerate() {
+        let b = (k - lo) as usize;
+        order[starts[b]] = i;
+        starts[b] += 1;
+    }
+    apply_order(v, order);
+}
+
+// smallest and largest key
+fn key_range<I: Iterator<Item = u64>>(keys: I) -> Option<(u64, u64)> {
+    let mut res: Option<(u64, u64)> = None;
+    for k in keys {
+        res = Some(match res {
+            None => (k, k),
+            Some((lo, hi)) => (lo.min(k), hi.max(k)),
+        });
+    }
+    res
+}
+
+// how many buckets lo..=hi takes, None if that is too many for n keys.
+// 0..=u64::MAX doesn't even fit in a usize
+fn counting_range(lo: u64, hi: u64, n: usize) -> Option<usize> {
+    let range = usize::try_from(hi - lo).ok()?.checked_add(1)?;
+    if range > n.saturating_mul(COUNTING_SPREAD).saturating_add(256) {
+        return None;
+    }
+    Some(range)
+}
+
+// MSD radix sort for byte strings, not stable.
+// Buckets on the first byte, then each bucket on the next,
+// keys that have run out go first so "ab" is before "abc".
+// Items are swapped into their buckets in place, American flag style
+pub fn msd_radix_sort<T: AsRef<[u8]>>(v: &mut [T]) {
+    msd_at(v, 0, &|t: &T| t.as_ref());
+}
+
+pub fn msd_radix_sort_by_key<T, F>(v: &mut [T], f: F)
+where
+    F: Fn(&T) -> &[u8],
+{
+    msd_at(v, 0, &f);
+}
+
+// Only the smaller buckets recurse, the largest is looped on.
+// Any bucket but the largest has at most half the items, so the
+// stack stays log n deep even when every key shares a long prefix
+fn msd_at<T, F>(mut v: &mut [T], mut d: usize, key: &F)
+where
+    F: Fn(&T) -> &[u8],
+{
+    loop {
+        if v.len() <= MSD_CUTOFF {
+            insertion_sort_at(
+                v,
+                0,
+                &mut |a: &T, b: &T| key(a)[d.min(key(a).len())..] < key(b)[d.min(key(b).len())..],
+                &NoOp,
+            );
+            return;
+        }
+
+        // bucket 0 is for keys with no byte d
+        let byte = |t: &T| -> usize {
+            let k = key(t);
+            if d < k.len() {
+                k[d] as usize + 1
+            } else {
+                0
+            }
+        };
+        let mut ends = [0usize; 257];
+        for t in v.iter() {
+            ends[byte(t)] += 1;
+        }
+        let mut starts = [0usize; 257];
+        let mut total = 0;
+        for b in 0..257 {
+            starts[b] = total;
+            total += ends[b];
+            ends[b] = total;
+        }
+
+        // next[b] is the first slot of bucket b not yet filled correctly
+        let mut next = starts;
+        for b in 0..257 {
+            while next[b] < ends[b] {
+                let c = byte(&v[next[b]]);
+                if c == b {
+                    next[b] += 1;
+                } else {
+                    v.swap(next[b], next[c]);
+                    next[c] += 1;
+                }
+            }
+        }
+
+        // keys that ended here are all equal, the rest go on to byte d + 1
+        let big = (1..257).max_by_key(|&b| ends[b] - starts[b]).unwrap();
+        for b in (1..257).filter(|&b| b != big) {
+            msd_at(&mut v[starts[b]..ends[b]], d + 1, key);
+        }
+        v = &mut std::mem::take(&mut v)[starts[big]..ends[big]];
+        d += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::b_rand::{Random, XorShift};
+
+    #[derive(Debug, PartialEq, Clone)]
+    struct Record {
+        id: u32,
+        name: String,
+    }
+
+    #[test]
+    fn test_radix_sort() {
+        // cargo test test_radix_sort -- --nocapture
+        let mut v = vec![4u32, 6, 1, 8, 11, 13, 3];
+        radix_sort(&mut v);
+
+        let sorted_v = vec![1, 3, 4, 6, 8, 11, 13];
+        assert_eq!(v, sorted_v);
+    }
+    #[test]
+    fn test_radix_sort_random() {
+        // cargo test test_radix_sort_random -- --nocapture
+        let mut r = XorShift::new(11);
+        let v: Vec<u64> = (0..10_000).map(|_| r.next_u64()).collect();
+        let mut sorted_v = v.clone();
+        sorted_v.sort();
+        let mut a = v.clone();
+        radix_sort(&mut a);
+        assert_eq!(a, sorted_v);
+
+        let v: Vec<u32> = v.iter().map(|x| *x as u32).collect();
+        let mut sorted_v = v.clone();
+        sorted_v.sort();
+        let mut a = v;
+        radix_sort(&mut a);
+        assert_eq!(a, sorted_v);
+    }
+    #[test]
+    fn test_radix_sort_signed() {
+        // cargo test test_radix_sort_signed -- --nocapture
+        let mut v = vec![5i32, -3, 0, i32::MIN, i32::MAX, -1, 7, -200];
+        let mut sorted_v = v.clone();
+        sorted_v.sort();
+        radix_sort(&mut v);
+        assert_eq!(v, sorted_v);
+
+        let mut r = XorShift::new(3);
+        let v: Vec<i64> = (0..5_000).map(|_| r.next_u64() as i64).collect();
+        let mut sorted_v = v.clone();
+        sorted_v.sort();
+        let mut a = v;
+        radix_sort(&mut a);
+        assert_eq!(a, sorted_v);
+
+        let mut v: Vec<i8> = (-128..=127).rev().collect();
+        radix_sort(&mut v);
+        assert_eq!(v, (-128..=127).collect::<Vec<i8>>());
+    }
+    #[test]
+    fn test_radix_sort_by_key_stable() {
+        // cargo test test_radix_sort_by_key_stable -- --nocapture
+        let v: Vec<Record> = (0..1_000)
+            .map(|i| Record {
+                id: (i * 7_919) % 37,
+                name: format!("r{}", i),
+            })
+            .collect();
+        let mut sorted_v = v.clone();
+        sorted_v.sort_by_key(|r| r.id);
+
+        let mut a = v.clone();
+        radix_sort_by_key(&mut a, |r| r.id);
+        assert_eq!(a, sorted_v);
+
+        let mut b = v;
+        counting_sort_by_key(&mut b, |r| r.id as i64 - 20);
+        assert_eq!(b, sorted_v);
+    }
+    #[test]
+    fn test_counting_sort() {
+        // cargo test test_counting_sort -- --nocapture
+        let mut v = vec![3i16, -2, 7, 3, 3, -2, 0, 7, 1];
+        let mut sorted_v = v.clone();
+        sorted_v.sort();
+        counting_sort(&mut v);
+        assert_eq!(v, sorted_v);
+
+        let mut v: Vec<u8> = Vec::new();
+        counting_sort(&mut v);
+        assert!(v.is_empty());
+
+        let mut v = vec![200u8; 10];
+        counting_sort(&mut v);
+        assert_eq!(v, vec![200u8; 10]);
+    }
+    #[test]
+    fn test_counting_sort_wide_keys() {
+        // cargo test test_counting_sort_wide_keys -- --nocapture
+        // max - min + 1 overflows, these have to go to radix sort
+        let mut v = vec![u64::MAX, 0, 7, u64::MAX, 0];
+        counting_sort(&mut v);
+        assert_eq!(v, vec![0, 0, 7, u64::MAX, u64::MAX]);
+        let mut v = vec![i64::MAX, i64::MIN, -1, 0];
+        counting_sort(&mut v);
+        assert_eq!(v, vec![i64::MIN, -1, 0, i64::MAX]);
+        // would be 2^32 buckets
+        let mut v = vec![u32::MAX, 3, 0, 3];
+        counting_sort(&mut v);
+        assert_eq!(v, vec![0, 3, 3, u32::MAX]);
+
+        // still stable when it falls back
+        let mut v: Vec<(u64, usize)> = [u64::MAX, 0, u64::MAX, 5, 0]
+            .iter()
+            .enumerate()
+            .map(|(i, &k)| (k, i))
+            .collect();
+        counting_sort_by_key(&mut v, |p| p.0);
+        assert_eq!(
+            v,
+            vec![(0, 1), (0, 4), (5, 3), (u64::MAX, 0), (u64::MAX, 2)]
+        );
+
+        assert_eq!(counting_range(0, u64::MAX, 10), None);
+        assert_eq!(counting_range(10, 10, 1), Some(1));
+        assert_eq!(counting_range(0, 1000, 100), Some(1001));
+        assert_eq!(counting_range(0, 1000, 10), None);
+    }
+    #[test]
+    fn test_msd_radix_sort() {
+        // cargo test test_msd_radix_sort -- --nocapture
+        let mut v: Vec<String> = vec![
+            "abc", "ab", "", "b", "abd", "a", "zz", "ab", "ba", "abcd", "aa", "b",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        let mut r = XorShift::new(5);
+        for _ in 0..2_000 {
+            let len = (r.next_u64() % 6) as usize;
+            let s: String = (0..len)
+                .map(|_| (b'a' + (r.next_u64() % 4) as u8) as char)
+                .collect();
+            v.push(s);
+        }
+        let mut sorted_v = v.clone();
+        sorted_v.sort();
+
+        msd_radix_sort(&mut v);
+        assert_eq!(v, sorted_v);
+
+        let mut b: Vec<&[u8]> = vec![b"\xff\x00", b"\x00", b"\xff", b""];
+        msd_radix_sort(&mut b);
+        assert_eq!(b, vec![&b""[..], b"\x00", b"\xff", b"\xff\x00"]);
+    }
+    #[test]
+    fn test_msd_radix_sort_by_key() {
+        // cargo test test_msd_radix_sort_by_key -- --nocapture
+        let mut v: Vec<Record> = (0..500)
+            .map(|i| Record {
+                id: i,
+                name: format!("n{}", (i * 31) % 97),
+            })
+            .collect();
+        msd_radix_sort_by_key(&mut v, |r| r.name.as_bytes());
+        assert!(v.windows(2).all(|w| w[0].name <= w[1].name));
+    }
+    #[test]
+    fn test_msd_radix_sort_long_prefix() {
+        // cargo test test_msd_radix_sort_long_prefix -- --nocapture
+        // one level a byte of the prefix would be 50k levels deep
+        let prefix = vec![b'x'; 50_000];
+        let mut r = XorShift::new(3);
+        let mut v: Vec<Vec<u8>> = (0..100)
+            .map(|_| {
+                let mut k = prefix.clone();
+                k.extend((0..r.below(4)).map(|_| b'a' + r.below(3) as u8));
+                k
+            })
+            .collect();
+        v.push(prefix[..1_000].to_vec());
+        let mut sorted_v = v.clone();
+        sorted_v.sort();
+        msd_radix_sort(&mut v);
+        assert!(v == sorted_v);
+    }
+}
@@ -0,0 +1,287 @@
+use std::fmt;
+
+use crate::b_rand::Random;
+
+// Checks that a generator looks random enough for picking pivots
+// and making test inputs. None of these prove anything, a generator
+// that fails one is broken, one that passes them all might still be.
+// See Knuth, The Art of Computer Programming vol 2, 3.3
+
+// z score a fair generator stays under 999 times in 1000
+const Z_999: f64 = 3.09;
+
+// Result of a chi-squared goodness of fit test
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChiSquared {
+    pub statistic: f64,
+    // degrees of freedom, one less than the buckets
+    pub df: usize,
+}
+
+impl ChiSquared {
+    // the statistic a fair generator stays under 999 times in 1000,
+    // from the Wilson Hilferty approximation
+    pub fn limit(&self) -> f64 {
+        let df = self.df as f64;
+        let a = 2.0 / (9.0 * df);
+        df * (1.0 - a + Z_999 * a.sqrt()).powi(3)
+    }
+
+    pub fn passes(&self) -> bool {
+        self.statistic < self.limit()
+    }
+}
+
+impl fmt::Display for ChiSquared {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "chi-squared {:.1} df {} limit {:.1}",
+            self.statistic,
+            self.df,
+            self.limit()
+        )
+    }
+}
+
+// Puts samples draws, each in 0..buckets, into buckets and measures
+// how far the counts are from the even spread expected.
+// Keep samples at 5 times buckets or more
+pub fn chi_squared<F: FnMut() -> u64>(buckets: usize, samples: usize, mut draw: F) -> ChiSquared {
+    assert!(buckets > 1, "chi squared needs 2 buckets or more");
+    let mut counts = vec![0usize; buckets];
+    for _ in 0..samples {
+        counts[draw() as usize] += 1;
+    }
+    let expected = samples as f64 / buckets as f64;
+    let statistic = counts
+        .iter()
+        .map(|&c| {
+            let d = c as f64 - expected;
+            d * d / expected
+        })
+        .sum();
+    ChiSquared {
+        statistic,
+        df: buckets - 1,
+    }
+}
+
+// Knuth's serial correlation of each draw with the next,
+// draws in 0..1. Near 0 for a good generator, an LCG with a
+// poor multiplier shows up here
+pub fn serial_correlation<F: FnMut() -> f64>(samples: usize, mut draw: F) -> f64 {
+    assert!(samples > 2, "serial correlation needs 3 draws or more");
+    let first = draw();
+    let (mut sum, mut sum_sq, mut sum_next) = (0.0, 0.0, 0.0);
+    let mut u = first;
+    for i in 0..samples {
+        // the last one pairs with the first
+        let next = if i + 1 == samples { first } else { draw() };
+        sum += u;
+        sum_sq += u * u;
+        sum_next += u * next;
+        u = next;
+    }
+    let n = samples as f64;
+    (n * sum_next - sum * sum) / (n * sum_sq - sum * sum)
+}
+
+// how far serial_correlation can be from 0 and still pass
+pub fn serial_correlation_limit(samples: usize) -> f64 {
+    let n = samples as f64;
+    1.0 / (n - 1.0) + Z_999 * (n * (n - 3.0) / (n + 1.0)).sqrt() / (n - 1.0)
+}
+
+// Brent's cycle finding, how many steps before the state from start
+// repeats. None if the cycle is longer than limit.
+// The state has to be everything the generator keeps, so Clone
+// and PartialEq on the generator itself
+pub fn period<R, F>(start: &R, mut step: F, limit: usize) -> Option<usize>
+where
+    R: Clone + PartialEq,
+    F: FnMut(&mut R),
+{
+    let mut power = 1;
+    let mut length = 1;
+    let mut tortoise = start.clone();
+    let mut hare = start.clone();
+    step(&mut hare);
+    while tortoise != hare {
+        // the hare has gone power steps past the tortoise, move the tortoise up
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
+        }
+        step(&mut hare);
+        length += 1;
+        if length > limit {
+            return None;
+        }
+    }
+    Some(length)
+}
+
+// Every check on one generator
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Report {
+    pub chi_squared: ChiSquared,
+    pub serial_correlation: f64,
+    pub samples: usize,
+    // None means longer than the limit it was checked to
+    pub period: Option<usize>,
+}
+
+impl Report {
+    pub fn passes(&self) -> bool {
+        self.chi_squared.passes()
+            && self.serial_correlation.abs() < serial_correlation_limit(self.samples)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} serial {:.5} period ",
+            self.chi_squared, self.serial_correlation
+        )?;
+        match self.period {
+            Some(p) => write!(f, "{}", p),
+            None => write!(f, "long"),
+        }
+    }
+}
+
+// Runs the checks on a copy of r, each draw split over 100 buckets
+// for chi squared. period only looks period_limit steps ahead
+pub fn check<R>(r: &R, samples: usize, period_limit: usize) -> Report
+where
+    R: Random + Clone + PartialEq,
+{
+    let mut g = r.clone();
+    let chi_squared = chi_squared(100, samples, || g.below(100));
+    let mut g = r.clone();
+    let serial_correlation = serial_correlation(samples, || g.next_f64());
+    let period = period(
+        r,
+        |g| {
+            g.next_u64();
+        },
+        period_limit,
+    );
+    Report {
+        chi_squared,
+        serial_correlation,
+        samples,
+        period,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::b_rand::{Pcg32, RandGen, XorShift, XorShiftStar};
+
+    // RandGen::new's modulus
+    const MODULO: usize = 23254544563;
+
+    #[test]
+    fn test_generators_pass() {
+        // cargo test test_generators_pass -- --nocapture
+        let reports = vec![
+            ("RandGen", check(&RandGen::new(12), 100_000, 1 << 16)),
+            ("XorShift", check(&XorShift::new(12), 100_000, 1 << 16)),
+            (
+                "XorShiftStar",
+                check(&XorShiftStar::new(12), 100_000, 1 << 16),
+            ),
+            ("Pcg32", check(&Pcg32::new(12, 1), 100_000, 1 << 16)),
+        ];
+        for (name, r) in reports {
+            println!("{:12} {}", name, r);
+            assert!(r.passes(), "{} {}", name, r);
+            assert_eq!(r.period, None, "{}", name);
+        }
+    }
+    #[test]
+    fn test_chi_squared_finds_modulo_bias() {
+        // cargo test test_chi_squared_finds_modulo_bias -- --nocapture
+        // step % max with max two thirds of the modulus makes
+        // the bottom half of 0..max twice as likely as the top
+        let max = MODULO / 3 * 2;
+        let mut r = RandGen::new(5);
+        let biased = chi_squared(10, 10_000, || (r.step() % max * 10 / max) as u64);
+        println!("step % max {}", biased);
+        assert!(!biased.passes());
+
+        let mut r = RandGen::new(5);
+        let fair = chi_squared(10, 10_000, || (r.next_v(max) * 10 / max) as u64);
+        println!("next_v {}", fair);
+        assert!(fair.passes());
+
+        // everything in one bucket
+        assert!(!chi_squared(10, 1_000, || 3).passes());
+    }
+    #[test]
+    fn test_serial_correlation() {
+        // cargo test test_serial_correlation -- --nocapture
+        let n = 10_000;
+        let mut r = Pcg32::new(1, 1);
+        let c = serial_correlation(n, || r.next_f64());
+        assert!(c.abs() < serial_correlation_limit(n), "{}", c);
+
+        // each value half the last plus a bit, strongly correlated
+        let mut r = XorShiftStar::new(1);
+        let mut u = 0.5;
+        let c = serial_correlation(n, || {
+            u = u / 2.0 + r.next_f64() / 2.0;
+            u
+        });
+        println!("correlated {}", c);
+        assert!(c > 0.4);
+
+        // the multiplier 3 mod 2^31 steps in small jumps
+        let mut r = RandGen::with_params(1, 3, 0, 1 << 31);
+        let c = serial_correlation(n, || r.step() as f64 / (1u64 << 31) as f64);
+        println!("small multiplier {}", c);
+        assert!(c.abs() > serial_correlation_limit(n));
+    }
+    #[test]
+    fn test_randgen_period() {
+        // cargo test test_randgen_period -- --nocapture
+        // the lcm of the multiplier's order mod 29, 397 and 2019851,
+        // 7, 198 and 1009925, so about 1 in 116 of the modulus
+        assert_eq!(lcg_period(&RandGen::new(12), MODULO), Some(199_965_150));
+    }
+    #[test]
+    fn test_period() {
+        // cargo test test_period -- --nocapture
+        // Hull Dobell, inc odd and mul - 1 a multiple of 4 is a full period mod 2^k
+        let full = RandGen::with_params(7, 5, 3, 1 << 12);
+        assert_eq!(lcg_period(&full, 1 << 20), Some(1 << 12));
+        assert_eq!(lcg_period(&full, 100), None);
+
+        // mul - 1 not a multiple of 4, half the states are never reached
+        let half = RandGen::with_params(7, 3, 3, 1 << 12);
+        assert_eq!(lcg_period(&half, 1 << 20), Some(1 << 11));
+
+        // mod 29 the multiplier has order 7, one part of RandGen's short period
+        let r = RandGen::with_params(1, 56394237 % 29, 34642349 % 29, 29);
+        assert_eq!(lcg_period(&r, 100), Some(7));
+
+        // a state that maps to itself
+        assert_eq!(period(&0u8, |_| {}, 10), Some(1));
+    }
+
+    fn lcg_period(r: &RandGen, limit: usize) -> Option<usize> {
+        period(
+            r,
+            |g| {
+                g.step();
+            },
+            limit,
+        )
+    }
+}
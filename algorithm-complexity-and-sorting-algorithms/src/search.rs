@@ -0,0 +1,349 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use crate::radix::RadixKey;
+use crate::timsort::gallop;
+
+// Searches over sorted slices.
+// The _by versions take f(item) -> how the item compares to the target,
+// the same way std's binary_search_by does.
+// Ok(i) means v[i] matches, Err(i) is where the target would be inserted
+
+// pred is true for a prefix of v and false after it, return the prefix length.
+// everything below is built on this, O(ln(n))
+pub fn partition_point<T, P>(v: &[T], mut pred: P) -> usize
+where
+    P: FnMut(&T) -> bool,
+{
+    let (mut lo, mut hi) = (0, v.len());
+    while lo < hi {
+        let m = lo + (hi - lo) / 2;
+        if pred(&v[m]) {
+            lo = m + 1;
+        } else {
+            hi = m;
+        }
+    }
+    lo
+}
+
+// Binary search, O(ln(n)).
+// with duplicates any one of the matches may be returned
+pub fn binary_search<T: PartialOrd>(v: &[T], x: &T) -> Result<usize, usize> {
+    binary_search_by(v, |a| cmp(a, x))
+}
+
+pub fn binary_search_by<T, F>(v: &[T], mut f: F) -> Result<usize, usize>
+where
+    F: FnMut(&T) -> Ordering,
+{
+    let (mut lo, mut hi) = (0, v.len());
+    while lo < hi {
+        let m = lo + (hi - lo) / 2;
+        match f(&v[m]) {
+            Ordering::Less => lo = m + 1,
+            Ordering::Greater => hi = m,
+            Ordering::Equal => return Ok(m),
+        }
+    }
+    Err(lo)
+}
+
+pub fn binary_search_by_key<T, K, F>(v: &[T], key: &K, mut f: F) -> Result<usize, usize>
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    binary_search_by(v, |a| cmp(&f(a), key))
+}
+
+// index of the first item not less than x
+pub fn lower_bound<T: PartialOrd>(v: &[T], x: &T) -> usize {
+    partition_point(v, |a| a < x)
+}
+
+pub fn lower_bound_by<T, F>(v: &[T], mut f: F) -> usize
+where
+    F: FnMut(&T) -> Ordering,
+{
+    partition_point(v, |a| f(a) == Ordering::Less)
+}
+
+pub fn lower_bound_by_key<T, K, F>(v: &[T], key: &K, mut f: F) -> usize
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    partition_point(v, |a| f(a) < *key)
+}
+
+// index of the first item greater than x
+pub fn upper_bound<T: PartialOrd>(v: &[T], x: &T) -> usize {
+    partition_point(v, |a| cmp(a, x) != Ordering::Greater)
+}
+
+pub fn upper_bound_by<T, F>(v: &[T], mut f: F) -> usize
+where
+    F: FnMut(&T) -> Ordering,
+{
+    partition_point(v, |a| f(a) != Ordering::Greater)
+}
+
+pub fn upper_bound_by_key<T, K, F>(v: &[T], key: &K, mut f: F) -> usize
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    partition_point(v, |a| cmp(&f(a), key) != Ordering::Greater)
+}
+
+// every index holding x, empty at the insertion point if there are none
+pub fn equal_range<T: PartialOrd>(v: &[T], x: &T) -> Range<usize> {
+    let lo = lower_bound(v, x);
+    lo..lo + upper_bound(&v[lo..], x)
+}
+
+pub fn equal_range_by<T, F>(v: &[T], mut f: F) -> Range<usize>
+where
+    F: FnMut(&T) -> Ordering,
+{
+    let lo = lower_bound_by(v, &mut f);
+    lo..lo + upper_bound_by(&v[lo..], f)
+}
+
+pub fn equal_range_by_key<T, K, F>(v: &[T], key: &K, mut f: F) -> Range<usize>
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    let lo = lower_bound_by_key(v, key, &mut f);
+    lo..lo + upper_bound_by_key(&v[lo..], key, f)
+}
+
+// Exponential search, probes 0, 1, 3, 7, 15 .. then binary searches the last gap.
+// O(ln(i)) where i is the answer, so finding things near the front
+// of a huge slice is cheap. Ok is always the first match
+pub fn exponential_search<T: PartialOrd>(v: &[T], x: &T) -> Result<usize, usize> {
+    exponential_search_by(v, |a| cmp(a, x))
+}
+
+pub fn exponential_search_by<T, F>(v: &[T], mut f: F) -> Result<usize, usize>
+where
+    F: FnMut(&T) -> Ordering,
+{
+    let i = gallop(v, &mut |_, a| f(a) == Ordering::Less, false);
+    found(i, v.get(i).map(f))
+}
+
+// Exponential search over data with no known end, a stream or a file.
+// get(i) is the i'th item or None once past the end,
+// items are fetched at O(ln(i)) indices and never past 2i + 1
+pub fn exponential_search_unbounded<T, G, F>(mut get: G, mut f: F) -> Result<usize, usize>
+where
+    G: FnMut(usize) -> Option<T>,
+    F: FnMut(&T) -> Ordering,
+{
+    // None sorts after everything
+    let mut less = |i: usize| get(i).map(|a| f(&a)).unwrap_or(Ordering::Greater);
+
+    // find a hi that is not less, everything below lo is less
+    let (mut lo, mut hi) = (0, 0);
+    while less(hi) == Ordering::Less {
+        lo = hi + 1;
+        hi = 2 * hi + 1;
+    }
+    while lo < hi {
+        let m = lo + (hi - lo) / 2;
+        if less(m) == Ordering::Less {
+            lo = m + 1;
+        } else {
+            hi = m;
+        }
+    }
+    found(lo, Some(less(lo)))
+}
+
+// Interpolation search for integers spread about evenly.
+// Guesses where x should be from the values at the ends
+// rather than always looking in the middle, O(ln(ln(n))) on uniform keys.
+// Skewed keys can make it O(n), so after a few guesses that
+// don't halve the range it falls back to binary search
+pub fn interpolation_search<T: RadixKey>(v: &[T], x: T) -> Result<usize, usize> {
+    let x = x.to_radix();
+    let (mut lo, mut hi) = (0, v.len());
+    let mut slow = 0;
+    while lo < hi {
+        let (a, b) = (v[lo].to_radix(), v[hi - 1].to_radix());
+        if x < a {
+            return Err(lo);
+        }
+        if x > b {
+            return Err(hi);
+        }
+        if a == b {
+            // x is between them so every item left matches
+            return Ok(lo);
+        }
+        let m = if slow >= 2 {
+            lo + (hi - lo) / 2
+        } else {
+            lo + ((x - a) as u128 * (hi - lo - 1) as u128 / (b - a) as u128) as usize
+        };
+        let before = hi - lo;
+        match v[m].to_radix().cmp(&x) {
+            Ordering::Less => lo = m + 1,
+            Ordering::Greater => hi = m,
+            Ordering::Equal => return Ok(m),
+        }
+        slow = if (hi - lo) * 2 > before { slow + 1 } else { 0 };
+    }
+    Err(lo)
+}
+
+// PartialOrd with anything unordered treated as equal
+fn cmp<T: PartialOrd>(a: &T, b: &T) -> Ordering {
+    a.partial_cmp(b).unwrap_or(Ordering::Equal)
+}
+
+fn found(i: usize, at: Option<Ordering>) -> Result<usize, usize> {
+    match at {
+        Some(Ordering::Equal) => Ok(i),
+        _ => Err(i),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::b_rand::XorShift;
+    use crate::{merge_sort, merge_sort_by_key, quick_sort};
+
+    // sorted with the crate's own sorts, lots of duplicates and gaps
+    fn sorted_inputs() -> Vec<Vec<u32>> {
+        let mut r = XorShift::new(21);
+        let v: Vec<u32> = (0..2_000)
+            .map(|_| (r.next_u64() % 1_000) as u32 * 2)
+            .collect();
+        let a = merge_sort(v.clone());
+        let mut b = v;
+        quick_sort(&mut b);
+        let uniform: Vec<u32> = (0..3_000).map(|_| r.next_u64() as u32).collect();
+        let uniform = merge_sort(uniform);
+        let mut skewed: Vec<u32> = (0..1_000).map(|i| i * i * i).collect();
+        quick_sort(&mut skewed);
+        vec![a, b, uniform, skewed, vec![], vec![5], vec![3; 50]]
+    }
+
+    fn queries(v: &[u32]) -> Vec<u32> {
+        let mut q = vec![0, 1, u32::MAX];
+        for x in v.iter().step_by(7) {
+            q.push(*x);
+            q.push(x.wrapping_add(1));
+            q.push(x.wrapping_sub(1));
+        }
+        q
+    }
+
+    // the answers worked out the slow way
+    fn linear_range(v: &[u32], x: u32) -> Range<usize> {
+        let lo = v.iter().take_while(|a| **a < x).count();
+        let hi = v.iter().take_while(|a| **a <= x).count();
+        lo..hi
+    }
+
+    fn check(v: &[u32], x: u32, res: Result<usize, usize>) {
+        let want = linear_range(v, x);
+        match res {
+            Ok(i) => assert!(want.contains(&i), "{} not in {:?} for {}", i, want, x),
+            Err(i) => {
+                assert!(want.is_empty(), "missed {}", x);
+                assert_eq!(i, want.start);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bounds() {
+        // cargo test test_bounds -- --nocapture
+        for v in sorted_inputs() {
+            for x in queries(&v) {
+                let want = linear_range(&v, x);
+                assert_eq!(lower_bound(&v, &x), want.start);
+                assert_eq!(upper_bound(&v, &x), want.end);
+                assert_eq!(equal_range(&v, &x), want);
+                assert_eq!(equal_range_by(&v, |a| a.cmp(&x)), want);
+                assert_eq!(lower_bound_by(&v, |a| a.cmp(&x)), want.start);
+                assert_eq!(upper_bound_by(&v, |a| a.cmp(&x)), want.end);
+            }
+        }
+    }
+    #[test]
+    fn test_binary_search() {
+        // cargo test test_binary_search -- --nocapture
+        for v in sorted_inputs() {
+            for x in queries(&v) {
+                check(&v, x, binary_search(&v, &x));
+                check(&v, x, binary_search_by(&v, |a| a.cmp(&x)));
+            }
+        }
+    }
+    #[test]
+    fn test_exponential_search() {
+        // cargo test test_exponential_search -- --nocapture
+        for v in sorted_inputs() {
+            for x in queries(&v) {
+                let want = linear_range(&v, x);
+                let res = exponential_search(&v, &x);
+                check(&v, x, res);
+                // always the first match
+                assert_eq!(res.unwrap_or_else(|i| i), want.start);
+
+                let res = exponential_search_unbounded(|i| v.get(i).cloned(), |a| a.cmp(&x));
+                assert_eq!(res, exponential_search(&v, &x));
+            }
+        }
+    }
+    #[test]
+    fn test_exponential_search_unbounded_probes() {
+        // cargo test test_exponential_search_unbounded_probes -- --nocapture
+        // an endless stream of even numbers, only a few get looked at
+        let mut probes = Vec::new();
+        let res = exponential_search_unbounded(
+            |i| {
+                probes.push(i);
+                Some(i as u64 * 2)
+            },
+            |a| a.cmp(&1_000),
+        );
+        assert_eq!(res, Ok(500));
+        println!("{:?}", probes);
+        assert!(probes.len() < 25);
+        assert!(probes.iter().all(|i| *i < 1_024));
+    }
+    #[test]
+    fn test_interpolation_search() {
+        // cargo test test_interpolation_search -- --nocapture
+        for v in sorted_inputs() {
+            for x in queries(&v) {
+                check(&v, x, interpolation_search(&v, x));
+            }
+        }
+        let v: Vec<i64> = (-500..500).map(|i| i * 3).collect();
+        assert_eq!(interpolation_search(&v, -1_500), Ok(0));
+        assert_eq!(interpolation_search(&v, 9), Ok(503));
+        assert_eq!(interpolation_search(&v, 10), Err(504));
+        assert_eq!(interpolation_search(&v, i64::MIN), Err(0));
+        assert_eq!(interpolation_search(&v, i64::MAX), Err(1_000));
+    }
+    #[test]
+    fn test_search_by_key() {
+        // cargo test test_search_by_key -- --nocapture
+        let v: Vec<(u32, &str)> = vec![(1, "a"), (3, "b"), (3, "c"), (3, "d"), (7, "e")];
+        let v = merge_sort_by_key(v, |p| p.0);
+        assert_eq!(equal_range_by_key(&v, &3, |p| p.0), 1..4);
+        assert_eq!(lower_bound_by_key(&v, &4, |p| p.0), 4);
+        assert_eq!(upper_bound_by_key(&v, &1, |p| p.0), 1);
+        assert_eq!(binary_search_by_key(&v, &7, |p| p.0), Ok(4));
+        assert_eq!(binary_search_by_key(&v, &0, |p| p.0), Err(0));
+    }
+}
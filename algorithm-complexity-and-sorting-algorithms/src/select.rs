@@ -0,0 +1,418 @@
+use std::cmp::Ordering;
+
+use crate::b_rand::{PivotRng, ThreadRng};
+use crate::introsort::{depth_limit, insertion_sort_at};
+use crate::observer::{NoOp, SortObserver};
+use crate::pivot_at;
+use crate::three_way::pivot_3way_at;
+
+// ranges this small are quicker to just sort
+const INSERTION_CUTOFF: usize = 16;
+
+// Quickselect, puts the item that belongs at k in a sorted v at v[k]
+// with everything before it not greater and everything after not less.
+// Only recurses into the side holding k so O(n) on average,
+// once the depth passes 2 log2(n) it switches to median of medians
+// so the worst case is O(n) too.
+// panics if k is out of range
+pub fn select_nth<T: PartialOrd>(v: &mut [T], k: usize) -> &T {
+    select_nth_observed(v, k, &NoOp)
+}
+
+pub fn select_nth_observed<'a, T: PartialOrd, O: SortObserver<T>>(
+    v: &'a mut [T],
+    k: usize,
+    obs: &O,
+) -> &'a T {
+    check_k(v, k);
+    let limit = depth_limit(v.len());
+    select_at(
+        v,
+        0,
+        k,
+        0,
+        limit,
+        &mut ThreadRng,
+        &mut |a: &T, b: &T| a < b,
+        obs,
+    );
+    &v[k]
+}
+
+pub fn select_nth_with_rng<'a, T: PartialOrd, R: PivotRng>(
+    v: &'a mut [T],
+    k: usize,
+    rng: &mut R,
+) -> &'a T {
+    check_k(v, k);
+    let limit = depth_limit(v.len());
+    select_at(v, 0, k, 0, limit, rng, &mut |a: &T, b: &T| a < b, &NoOp);
+    &v[k]
+}
+
+pub fn select_nth_by<T, F>(v: &mut [T], k: usize, mut compare: F) -> &T
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    check_k(v, k);
+    let limit = depth_limit(v.len());
+    select_at(
+        v,
+        0,
+        k,
+        0,
+        limit,
+        &mut ThreadRng,
+        &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    );
+    &v[k]
+}
+
+pub fn select_nth_by_key<T, K, F>(v: &mut [T], k: usize, mut f: F) -> &T
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    check_k(v, k);
+    let limit = depth_limit(v.len());
+    select_at(
+        v,
+        0,
+        k,
+        0,
+        limit,
+        &mut ThreadRng,
+        &mut |a: &T, b: &T| f(a) < f(b),
+        &NoOp,
+    );
+    &v[k]
+}
+
+// Same result as select_nth but never random, always O(n).
+// The constant is a lot bigger so select_nth is usually quicker
+pub fn median_of_medians<T: PartialOrd>(v: &mut [T], k: usize) -> &T {
+    check_k(v, k);
+    mom_select_at(v, 0, k, &mut |a: &T, b: &T| a < b, &NoOp);
+    &v[k]
+}
+
+// the lower median, None when v is empty
+pub fn median<T: PartialOrd>(v: &mut [T]) -> Option<&T> {
+    if v.is_empty() {
+        return None;
+    }
+    let k = (v.len() - 1) / 2;
+    Some(select_nth(v, k))
+}
+
+// Moves the k largest items to the front and returns them.
+// They are in no particular order, sort the prefix if that matters,
+// it is still cheaper than sorting all of v.
+// k past the end returns all of v
+pub fn top_k<T: PartialOrd>(v: &mut [T], k: usize) -> &mut [T] {
+    prefix_at(v, k, &mut |a: &T, b: &T| b < a)
+}
+
+pub fn top_k_by<T, F>(v: &mut [T], k: usize, mut compare: F) -> &mut [T]
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    prefix_at(v, k, &mut |a: &T, b: &T| compare(a, b) == Ordering::Greater)
+}
+
+pub fn top_k_by_key<T, K, F>(v: &mut [T], k: usize, mut f: F) -> &mut [T]
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    prefix_at(v, k, &mut |a: &T, b: &T| f(b) < f(a))
+}
+
+// Moves the k smallest items to the front and returns them, in no particular order
+pub fn bottom_k<T: PartialOrd>(v: &mut [T], k: usize) -> &mut [T] {
+    prefix_at(v, k, &mut |a: &T, b: &T| a < b)
+}
+
+pub fn bottom_k_by<T, F>(v: &mut [T], k: usize, mut compare: F) -> &mut [T]
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    prefix_at(v, k, &mut |a: &T, b: &T| compare(a, b) == Ordering::Less)
+}
+
+pub fn bottom_k_by_key<T, K, F>(v: &mut [T], k: usize, mut f: F) -> &mut [T]
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    prefix_at(v, k, &mut |a: &T, b: &T| f(a) < f(b))
+}
+
+fn check_k<T>(v: &[T], k: usize) {
+    assert!(
+        k < v.len(),
+        "select index {} out of range for length {}",
+        k,
+        v.len()
+    );
+}
+
+// the k items that sort first under is_less end up in v[..k]
+fn prefix_at<'a, T, F>(v: &'a mut [T], k: usize, is_less: &mut F) -> &'a mut [T]
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    let k = k.min(v.len());
+    if k > 0 {
+        let limit = depth_limit(v.len());
+        select_at(v, 0, k - 1, 0, limit, &mut ThreadRng, is_less, &NoOp);
+    }
+    &mut v[..k]
+}
+
+#[allow(clippy::too_many_arguments)]
+fn select_at<T, R, F, O>(
+    v: &mut [T],
+    off: usize,
+    k: usize,
+    depth: usize,
+    limit: usize,
+    rng: &mut R,
+    is_less: &mut F,
+    obs: &O,
+) where
+    R: PivotRng,
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    obs.depth(depth);
+    if v.len() <= INSERTION_CUTOFF {
+        insertion_sort_at(v, off, is_less, obs);
+        return;
+    }
+    if depth >= limit {
+        mom_select_at(v, off, k, is_less, obs);
+        return;
+    }
+
+    let p = pivot_at(v, off, rng, is_less, obs);
+    obs.pass(v);
+
+    // only the side holding k is looked at again
+    match k.cmp(&p) {
+        Ordering::Equal => {}
+        Ordering::Less => select_at(&mut v[..p], off, k, depth + 1, limit, rng, is_less, obs),
+        Ordering::Greater => select_at(
+            &mut v[p + 1..],
+            off + p + 1,
+            k - p - 1,
+            depth + 1,
+            limit,
+            rng,
+            is_less,
+            obs,
+        ),
+    }
+}
+
+// Median of medians, the pivot is always somewhere in the middle 3/10 - 7/10
+// so each step throws away at least 3/10 of v.
+// Partitions 3 ways so runs of equal keys can't slow it down
+fn mom_select_at<T, F, O>(v: &mut [T], off: usize, k: usize, is_less: &mut F, obs: &O)
+where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    if v.len() <= INSERTION_CUTOFF {
+        insertion_sort_at(v, off, is_less, obs);
+        return;
+    }
+    let m = mom_pivot(v, off, is_less, obs);
+    let (lt, gt) = pivot_3way_at(v, off, &mut Fixed(m), is_less, obs);
+    obs.pass(v);
+
+    if k < lt {
+        mom_select_at(&mut v[..lt], off, k, is_less, obs);
+    } else if k >= gt {
+        mom_select_at(&mut v[gt..], off + gt, k - gt, is_less, obs);
+    }
+}
+
+// sort each group of 5, move the group medians to the front
+// then find the median of those the same way
+fn mom_pivot<T, F, O>(v: &mut [T], off: usize, is_less: &mut F, obs: &O) -> usize
+where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    let groups = v.len() / 5;
+    for g in 0..groups {
+        let s = g * 5;
+        insertion_sort_at(&mut v[s..s + 5], off + s, is_less, obs);
+        obs.swap(off + g, off + s + 2);
+        v.swap(g, s + 2);
+    }
+    let m = groups / 2;
+    mom_select_at(&mut v[..groups], off, m, is_less, obs);
+    m
+}
+
+// a "generator" that always picks the same index
+struct Fixed(usize);
+
+impl PivotRng for Fixed {
+    fn next_below(&mut self, _max: usize) -> usize {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::b_rand::XorShift;
+    use crate::observer::Counter;
+
+    // the worst possible pivots, always the 1st item
+    struct First;
+
+    impl PivotRng for First {
+        fn next_below(&mut self, _max: usize) -> usize {
+            0
+        }
+    }
+
+    fn inputs() -> Vec<Vec<u32>> {
+        let mut r = XorShift::new(13);
+        vec![
+            vec![7],
+            vec![4, 6, 1, 8, 11, 13, 3],
+            (0..1_000).map(|_| r.next_u64() as u32).collect(),
+            (0..1_000).map(|_| (r.next_u64() % 5) as u32).collect(),
+            (0..500).collect(),
+            (0..500).rev().collect(),
+            vec![9; 300],
+        ]
+    }
+
+    fn is_selected<T: PartialOrd>(v: &[T], k: usize) -> bool {
+        v[..k].iter().all(|x| *x <= v[k]) && v[k + 1..].iter().all(|x| *x >= v[k])
+    }
+
+    #[test]
+    fn test_select_nth() {
+        // cargo test test_select_nth -- --nocapture
+        for v in inputs() {
+            let mut sorted_v = v.clone();
+            sorted_v.sort();
+            for k in [0, v.len() / 3, v.len() / 2, v.len() - 1].iter().cloned() {
+                let mut a = v.clone();
+                assert_eq!(*select_nth(&mut a, k), sorted_v[k]);
+                assert!(is_selected(&a, k));
+
+                let mut a = v.clone();
+                assert_eq!(*median_of_medians(&mut a, k), sorted_v[k]);
+                assert!(is_selected(&a, k));
+
+                let mut a = v.clone();
+                let x = *select_nth_by(&mut a, k, |a, b| b.cmp(a));
+                assert_eq!(x, sorted_v[v.len() - 1 - k]);
+            }
+        }
+    }
+    #[test]
+    fn test_select_nth_by_key() {
+        // cargo test test_select_nth_by_key -- --nocapture
+        let mut v: Vec<(u32, &str)> = vec![(3, "c"), (1, "a"), (4, "d"), (2, "b"), (5, "e")];
+        assert_eq!(select_nth_by_key(&mut v, 1, |p| p.0).1, "b");
+        assert_eq!(median(&mut [5, 1, 4, 2]), Some(&2));
+        assert_eq!(median::<u32>(&mut []), None);
+    }
+    #[test]
+    fn test_select_nth_bad_pivots() {
+        // cargo test test_select_nth_bad_pivots -- --nocapture
+        // always picking the 1st item on sorted input is quadratic for quickselect,
+        // the median of medians fallback keeps it linear
+        let n = 20_000;
+        let mut v: Vec<u32> = (0..n).collect();
+        let c = Counter::new();
+        let limit = depth_limit(v.len());
+        select_at(
+            &mut v,
+            0,
+            n as usize / 2,
+            0,
+            limit,
+            &mut First,
+            &mut |a: &u32, b: &u32| a < b,
+            &c,
+        );
+        assert_eq!(v[n as usize / 2], n / 2);
+        let t = c.totals();
+        println!("{:?}", t);
+        assert!(t.comparisons < 50 * n as usize);
+        assert!(t.max_depth <= limit);
+    }
+    #[test]
+    fn test_select_nth_with_rng() {
+        // cargo test test_select_nth_with_rng -- --nocapture
+        let v = inputs().remove(2);
+        let mut a = v.clone();
+        let mut b = v;
+        select_nth_with_rng(&mut a, 100, &mut XorShift::new(1));
+        select_nth_with_rng(&mut b, 100, &mut XorShift::new(1));
+        assert_eq!(a, b);
+    }
+    #[test]
+    #[should_panic]
+    fn test_select_nth_out_of_range() {
+        // cargo test test_select_nth_out_of_range -- --nocapture
+        select_nth(&mut [1, 2, 3], 3);
+    }
+    #[test]
+    fn test_top_k() {
+        // cargo test test_top_k -- --nocapture
+        for v in inputs() {
+            let mut sorted_v = v.clone();
+            sorted_v.sort();
+            for k in [0, 1, 10, v.len(), v.len() + 5].iter().cloned() {
+                let k_in = k.min(v.len());
+
+                let mut a = v.clone();
+                let top = top_k(&mut a, k).to_vec();
+                assert_eq!(top.len(), k_in);
+                let mut top_sorted = top.clone();
+                top_sorted.sort();
+                assert_eq!(top_sorted, sorted_v[v.len() - k_in..].to_vec());
+
+                let mut a = v.clone();
+                let mut bottom = bottom_k(&mut a, k).to_vec();
+                bottom.sort();
+                assert_eq!(bottom, sorted_v[..k_in].to_vec());
+            }
+        }
+    }
+    #[test]
+    fn test_top_k_by_key() {
+        // cargo test test_top_k_by_key -- --nocapture
+        let mut v: Vec<(&str, u32)> = vec![("a", 30), ("b", 90), ("c", 10), ("d", 70), ("e", 50)];
+        let mut top: Vec<&str> = top_k_by_key(&mut v, 2, |p| p.1)
+            .iter()
+            .map(|p| p.0)
+            .collect();
+        top.sort();
+        assert_eq!(top, vec!["b", "d"]);
+
+        let mut bottom: Vec<&str> = bottom_k_by_key(&mut v, 2, |p| p.1)
+            .iter()
+            .map(|p| p.0)
+            .collect();
+        bottom.sort();
+        assert_eq!(bottom, vec!["a", "c"]);
+
+        let mut v = vec![3, 1, 2];
+        let mut a = top_k_by(&mut v, 1, |a, b| a.cmp(b)).to_vec();
+        a.extend_from_slice(bottom_k_by(&mut v, 1, |a, b| a.cmp(b)));
+        assert_eq!(a, vec![3, 1]);
+    }
+}
@@ -0,0 +1,324 @@
+use std::cmp::Ordering;
+
+use crate::introsort::insertion_sort_at;
+use crate::observer::{NoOp, SortObserver};
+
+// The simple O(n^2) sorts, next to bubble sort these are the
+// baselines the rest of the crate is measured against
+
+// Insertion sort, Big O n^2 but O(n) on sorted input, stable
+pub fn insertion_sort<T: PartialOrd>(v: &mut [T]) {
+    insertion_sort_observed(v, &NoOp);
+}
+
+pub fn insertion_sort_observed<T: PartialOrd, O: SortObserver<T>>(v: &mut [T], obs: &O) {
+    insertion_sort_at(v, 0, &mut |a: &T, b: &T| a < b, obs);
+    obs.pass(v);
+}
+
+pub fn insertion_sort_by<T, F>(v: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    insertion_sort_at(
+        v,
+        0,
+        &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    );
+}
+
+pub fn insertion_sort_by_key<T, K, F>(v: &mut [T], mut f: F)
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    insertion_sort_at(v, 0, &mut |a: &T, b: &T| f(a) < f(b), &NoOp);
+}
+
+// Selection sort, Big O n^2 always but never more than n swaps.
+// not stable, the swap can jump an item past its equals
+pub fn selection_sort<T: PartialOrd>(v: &mut [T]) {
+    selection_sort_observed(v, &NoOp);
+}
+
+pub fn selection_sort_observed<T: PartialOrd, O: SortObserver<T>>(v: &mut [T], obs: &O) {
+    selection_sort_impl(v, &mut |a: &T, b: &T| a < b, obs);
+}
+
+pub fn selection_sort_by<T, F>(v: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    selection_sort_impl(
+        v,
+        &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    );
+}
+
+pub fn selection_sort_by_key<T, K, F>(v: &mut [T], mut f: F)
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    selection_sort_impl(v, &mut |a: &T, b: &T| f(a) < f(b), &NoOp);
+}
+
+fn selection_sort_impl<T, F, O>(v: &mut [T], is_less: &mut F, obs: &O)
+where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    for i in 0..v.len() {
+        // find the smallest of what is left and put it at i
+        let mut min = i;
+        for j in i + 1..v.len() {
+            obs.compare(j, min);
+            if is_less(&v[j], &v[min]) {
+                min = j;
+            }
+        }
+        if min != i {
+            obs.swap(i, min);
+            v.swap(i, min);
+        }
+        obs.pass(v);
+    }
+}
+
+// Shell sort, insertion sort over items gap apart with
+// the gap shrinking to 1, so items move far in few swaps.
+// Knuth's gaps 1, 4, 13, 40 .. give O(n^1.5) worst case, not stable
+pub fn shell_sort<T: PartialOrd>(v: &mut [T]) {
+    shell_sort_observed(v, &NoOp);
+}
+
+pub fn shell_sort_observed<T: PartialOrd, O: SortObserver<T>>(v: &mut [T], obs: &O) {
+    shell_sort_impl(v, &mut |a: &T, b: &T| a < b, obs);
+}
+
+pub fn shell_sort_by<T, F>(v: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    shell_sort_impl(
+        v,
+        &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    );
+}
+
+pub fn shell_sort_by_key<T, K, F>(v: &mut [T], mut f: F)
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    shell_sort_impl(v, &mut |a: &T, b: &T| f(a) < f(b), &NoOp);
+}
+
+fn shell_sort_impl<T, F, O>(v: &mut [T], is_less: &mut F, obs: &O)
+where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    let mut gap = 1;
+    while gap < v.len() / 3 {
+        gap = 3 * gap + 1;
+    }
+    while gap > 0 {
+        for i in gap..v.len() {
+            let mut j = i;
+            while j >= gap {
+                obs.compare(j, j - gap);
+                if !is_less(&v[j], &v[j - gap]) {
+                    break;
+                }
+                obs.swap(j - gap, j);
+                v.swap(j - gap, j);
+                j -= gap;
+            }
+        }
+        obs.pass(v);
+        gap /= 3;
+    }
+}
+
+// Cocktail shaker sort, bubble sort going both ways.
+// Small items near the end, the turtles that make bubble sort slow,
+// come back in one pass. Big O n^2, stable
+pub fn cocktail_sort<T: PartialOrd>(v: &mut [T]) {
+    cocktail_sort_observed(v, &NoOp);
+}
+
+pub fn cocktail_sort_observed<T: PartialOrd, O: SortObserver<T>>(v: &mut [T], obs: &O) {
+    cocktail_sort_impl(v, &mut |a: &T, b: &T| a < b, obs);
+}
+
+pub fn cocktail_sort_by<T, F>(v: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    cocktail_sort_impl(
+        v,
+        &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    );
+}
+
+pub fn cocktail_sort_by_key<T, K, F>(v: &mut [T], mut f: F)
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    cocktail_sort_impl(v, &mut |a: &T, b: &T| f(a) < f(b), &NoOp);
+}
+
+fn cocktail_sort_impl<T, F, O>(v: &mut [T], is_less: &mut F, obs: &O)
+where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    if v.len() < 2 {
+        return;
+    }
+    // everything outside lo..=hi is in its final place
+    let (mut lo, mut hi) = (0, v.len() - 1);
+    while lo < hi {
+        // forwards, the biggest goes to hi
+        let mut last = lo;
+        for i in lo..hi {
+            obs.compare(i, i + 1);
+            if is_less(&v[i + 1], &v[i]) {
+                obs.swap(i, i + 1);
+                v.swap(i, i + 1);
+                last = i;
+            }
+        }
+        obs.pass(v);
+        hi = last;
+
+        // backwards, the smallest goes to lo
+        let mut first = hi;
+        for i in (lo..hi).rev() {
+            obs.compare(i, i + 1);
+            if is_less(&v[i + 1], &v[i]) {
+                obs.swap(i, i + 1);
+                v.swap(i, i + 1);
+                first = i + 1;
+            }
+        }
+        obs.pass(v);
+        lo = first;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::b_rand::XorShift;
+    use crate::observer::Counter;
+
+    type Sort = fn(&mut [u32]);
+
+    fn sorts() -> Vec<(&'static str, Sort)> {
+        vec![
+            ("insertion", insertion_sort),
+            ("selection", selection_sort),
+            ("shell", shell_sort),
+            ("cocktail", cocktail_sort),
+        ]
+    }
+
+    #[test]
+    fn test_simple_sorts() {
+        // cargo test test_simple_sorts -- --nocapture
+        let mut r = XorShift::new(4);
+        let random: Vec<u32> = (0..1_000).map(|_| (r.next_u64() % 300) as u32).collect();
+        let inputs = vec![
+            vec![],
+            vec![1],
+            vec![2, 1],
+            vec![4, 6, 1, 8, 11, 13, 3],
+            random,
+            (0..300).collect(),
+            (0..300).rev().collect(),
+            vec![7; 50],
+        ];
+        for (name, sort) in sorts() {
+            for v in &inputs {
+                let mut sorted_v = v.clone();
+                sorted_v.sort();
+                let mut a = v.clone();
+                sort(&mut a);
+                assert_eq!(a, sorted_v, "{}", name);
+            }
+        }
+    }
+    #[test]
+    fn test_simple_sorts_by() {
+        // cargo test test_simple_sorts_by -- --nocapture
+        let v = vec![4, 6, 1, 8, 11, 13, 3];
+        let desc = vec![13, 11, 8, 6, 4, 3, 1];
+        let mut a = v.clone();
+        insertion_sort_by(&mut a, |a, b| b.cmp(a));
+        assert_eq!(a, desc);
+        let mut a = v.clone();
+        selection_sort_by(&mut a, |a, b| b.cmp(a));
+        assert_eq!(a, desc);
+        let mut a = v.clone();
+        shell_sort_by(&mut a, |a, b| b.cmp(a));
+        assert_eq!(a, desc);
+        let mut a = v;
+        cocktail_sort_by(&mut a, |a, b| b.cmp(a));
+        assert_eq!(a, desc);
+    }
+    #[test]
+    fn test_simple_sorts_stable() {
+        // cargo test test_simple_sorts_stable -- --nocapture
+        let v: Vec<(u32, usize)> = (0..200).map(|i| ((i as u32 * 7) % 5, i)).collect();
+        let mut sorted_v = v.clone();
+        sorted_v.sort_by_key(|p| p.0);
+
+        let mut a = v.clone();
+        insertion_sort_by_key(&mut a, |p| p.0);
+        assert_eq!(a, sorted_v);
+        let mut a = v.clone();
+        cocktail_sort_by_key(&mut a, |p| p.0);
+        assert_eq!(a, sorted_v);
+
+        // these still sort by key, just not keeping the order of equals
+        let mut a = v.clone();
+        selection_sort_by_key(&mut a, |p| p.0);
+        assert!(a.windows(2).all(|w| w[0].0 <= w[1].0));
+        let mut a = v;
+        shell_sort_by_key(&mut a, |p| p.0);
+        assert!(a.windows(2).all(|w| w[0].0 <= w[1].0));
+    }
+    #[test]
+    fn test_simple_sorts_counts() {
+        // cargo test test_simple_sorts_counts -- --nocapture
+        let n = 1_000;
+        let sorted_v: Vec<u32> = (0..n).collect();
+        let reversed: Vec<u32> = (0..n).rev().collect();
+
+        // selection sort never does more than n swaps
+        let c = Counter::new();
+        selection_sort_observed(&mut reversed.clone(), &c);
+        assert!(c.totals().swaps <= n as usize);
+
+        // insertion and cocktail only need one look at sorted input
+        let c = Counter::new();
+        insertion_sort_observed(&mut sorted_v.clone(), &c);
+        assert_eq!(c.totals().comparisons, n as usize - 1);
+        let c = Counter::new();
+        cocktail_sort_observed(&mut sorted_v.clone(), &c);
+        assert_eq!(c.totals().comparisons, n as usize - 1);
+
+        // shell sort gets reversed input done far under n^2 / 2
+        let c = Counter::new();
+        shell_sort_observed(&mut reversed.clone(), &c);
+        println!("shell {}", c.totals());
+        assert!(c.totals().comparisons < (n * n / 20) as usize);
+    }
+}
@@ -0,0 +1,274 @@
+use std::fmt;
+
+use crate::heap::heap_sort_observed;
+use crate::merge::{merge_sort_bottom_up_observed, merge_sort_rayon, merge_sort_slice_observed};
+use crate::observer::{NoOp, SortObserver};
+use crate::radix::{counting_sort, radix_sort, RadixKey};
+use crate::simple::{
+    cocktail_sort_observed, insertion_sort_observed, selection_sort_observed, shell_sort_observed,
+};
+use crate::three_way::{quick_sort_3way_observed, quick_sort_3way_rayon_observed};
+use crate::{
+    bubble_sort_observed, intro_sort_observed, quick_sort_observed, quick_sort_rayon_observed,
+    threaded_quick_sort_observed, tim_sort_observed,
+};
+
+// Growth of the work done for n items
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Complexity {
+    N,
+    NLogN,
+    // n^1.5
+    N32,
+    N2,
+    // k is the bytes in the key for radix sort
+    NK,
+    // k is the range of the keys for counting sort
+    NPlusK,
+}
+
+impl fmt::Display for Complexity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Complexity::N => "O(n)",
+            Complexity::NLogN => "O(n ln(n))",
+            Complexity::N32 => "O(n^1.5)",
+            Complexity::N2 => "O(n^2)",
+            Complexity::NK => "O(n k)",
+            Complexity::NPlusK => "O(n + k)",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// What a sort promises, for tests and benchmarks to pick from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortInfo {
+    pub name: &'static str,
+    // equal items keep their order
+    pub stable: bool,
+    // no more than O(ln(n)) extra memory
+    pub in_place: bool,
+    // uses more than one thread
+    pub parallel: bool,
+    // reports to sort_observed, false means counts stay at 0
+    pub observed: bool,
+    pub worst: Complexity,
+    pub average: Complexity,
+}
+
+// One way in to every sort in the crate
+pub trait Sorter<T> {
+    fn info(&self) -> SortInfo;
+
+    fn sort(&self, v: &mut [T]);
+
+    // events go to obs, sorts that have no observed
+    // version just sort, check info().observed before trusting counts
+    fn sort_observed(&self, v: &mut [T], _obs: &(dyn SortObserver<T> + Sync)) {
+        self.sort(v);
+    }
+}
+
+type Observed<T> = fn(&mut [T], &(dyn SortObserver<T> + Sync));
+
+// a sort from this crate, as a pair of fn pointers
+pub struct Algorithm<T> {
+    info: SortInfo,
+    sort: fn(&mut [T]),
+    observed: Option<Observed<T>>,
+}
+
+impl<T> Sorter<T> for Algorithm<T> {
+    fn info(&self) -> SortInfo {
+        self.info
+    }
+
+    fn sort(&self, v: &mut [T]) {
+        (self.sort)(v);
+    }
+
+    fn sort_observed(&self, v: &mut [T], obs: &(dyn SortObserver<T> + Sync)) {
+        match self.observed {
+            Some(f) => f(v, obs),
+            None => (self.sort)(v),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Algorithm<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Algorithm({:?})", self.info)
+    }
+}
+
+// A row of the registry, flags are any of stable in_place parallel.
+// observed sorts get their plain version from the NoOp observer
+macro_rules! algorithm {
+    ($name:expr, [$($flag:ident)*], $worst:ident, $average:ident, observed $f:path) => {
+        Algorithm {
+            info: info($name, &[$(stringify!($flag),)* "observed"], Complexity::$worst, Complexity::$average),
+            sort: |v| $f(v, &NoOp),
+            observed: Some(|v, obs| $f(v, &obs)),
+        }
+    };
+    ($name:expr, [$($flag:ident)*], $worst:ident, $average:ident, plain $f:path) => {
+        Algorithm {
+            info: info($name, &[$(stringify!($flag)),*], Complexity::$worst, Complexity::$average),
+            sort: $f,
+            observed: None,
+        }
+    };
+}
+
+fn info(name: &'static str, flags: &[&str], worst: Complexity, average: Complexity) -> SortInfo {
+    SortInfo {
+        name,
+        stable: flags.contains(&"stable"),
+        in_place: flags.contains(&"in_place"),
+        parallel: flags.contains(&"parallel"),
+        observed: flags.contains(&"observed"),
+        worst,
+        average,
+    }
+}
+
+// Every comparison sort, slowest first.
+// merge is the slice version of merge_sort, the Vec one
+// is the same algorithm but can't sort a borrowed slice.
+// The quadratic ones are here too, skip them by info().worst for big inputs
+pub fn sorters<'a, T>() -> Vec<Box<dyn Sorter<T> + 'a>>
+where
+    T: PartialOrd + Send + Sync + 'a,
+{
+    #[rustfmt::skip]
+    let all: Vec<Algorithm<T>> = vec![
+        algorithm!("bubble", [stable in_place], N2, N2, observed bubble_sort_observed),
+        algorithm!("cocktail", [stable in_place], N2, N2, observed cocktail_sort_observed),
+        algorithm!("selection", [in_place], N2, N2, observed selection_sort_observed),
+        algorithm!("insertion", [stable in_place], N2, N2, observed insertion_sort_observed),
+        algorithm!("shell", [in_place], N32, N32, observed shell_sort_observed),
+        algorithm!("merge", [stable], NLogN, NLogN, observed merge_sort_slice_observed),
+        algorithm!("merge_bottom_up", [stable], NLogN, NLogN, observed merge_sort_bottom_up_observed),
+        algorithm!("merge_rayon", [stable parallel], NLogN, NLogN, plain merge_sort_rayon),
+        algorithm!("tim", [stable], NLogN, NLogN, observed tim_sort_observed),
+        algorithm!("quick", [in_place], N2, NLogN, observed quick_sort_observed),
+        algorithm!("quick_3way", [in_place], N2, NLogN, observed quick_sort_3way_observed),
+        algorithm!("intro", [in_place], NLogN, NLogN, observed intro_sort_observed),
+        algorithm!("heap", [in_place], NLogN, NLogN, observed heap_sort_observed),
+        algorithm!("threaded", [in_place parallel], N2, NLogN, observed threaded_quick_sort_observed),
+        algorithm!("rayon", [in_place parallel], N2, NLogN, observed quick_sort_rayon_observed),
+        algorithm!("rayon_3way", [in_place parallel], N2, NLogN, observed quick_sort_3way_rayon_observed),
+    ];
+    all.into_iter()
+        .map(|a| Box::new(a) as Box<dyn Sorter<T> + 'a>)
+        .collect()
+}
+
+// sorters plus the ones that only work on integers
+pub fn int_sorters<'a, T>() -> Vec<Box<dyn Sorter<T> + 'a>>
+where
+    T: RadixKey + PartialOrd + Send + Sync + 'a,
+{
+    let mut all = sorters();
+    all.push(Box::new(
+        algorithm!("radix", [stable], NK, NK, plain radix_sort),
+    ));
+    all.push(Box::new(
+        algorithm!("counting", [stable], NPlusK, NPlusK, plain counting_sort),
+    ));
+    all
+}
+
+// look a sort up by its info().name
+pub fn sorter<'a, T>(name: &str) -> Option<Box<dyn Sorter<T> + 'a>>
+where
+    T: PartialOrd + Send + Sync + 'a,
+{
+    sorters().into_iter().find(|s| s.info().name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::b_rand::XorShift;
+    use crate::observer::Counter;
+
+    #[test]
+    fn test_every_sorter() {
+        // cargo test test_every_sorter -- --nocapture
+        let mut r = XorShift::new(17);
+        let v: Vec<u32> = (0..2_000).map(|_| (r.next_u64() % 500) as u32).collect();
+        let mut sorted_v = v.clone();
+        sorted_v.sort();
+        let all = int_sorters::<u32>();
+        assert_eq!(all.len(), 18);
+        for s in all {
+            let mut a = v.clone();
+            s.sort(&mut a);
+            assert_eq!(a, sorted_v, "{}", s.info().name);
+
+            let mut a = v.clone();
+            let c = Counter::new();
+            s.sort_observed(&mut a, &c);
+            assert_eq!(a, sorted_v, "{}", s.info().name);
+            assert_eq!(c.totals().comparisons > 0, s.info().observed);
+            println!("{:16} {:12} {}", s.info().name, s.info().worst, c.totals());
+        }
+    }
+    #[test]
+    fn test_sorter_info_stable() {
+        // cargo test test_sorter_info_stable -- --nocapture
+        // every sort that says it is stable has to be
+        let v: Vec<(u32, usize)> = (0..500).map(|i| ((i as u32 * 31) % 7, i)).collect();
+        let mut sorted_v = v.clone();
+        sorted_v.sort_by_key(|p| p.0);
+
+        #[derive(Debug, Clone, Copy)]
+        struct ByKey((u32, usize));
+        impl PartialEq for ByKey {
+            fn eq(&self, o: &Self) -> bool {
+                (self.0).0 == (o.0).0
+            }
+        }
+        impl PartialOrd for ByKey {
+            fn partial_cmp(&self, o: &Self) -> Option<std::cmp::Ordering> {
+                (self.0).0.partial_cmp(&(o.0).0)
+            }
+        }
+
+        let stable: Vec<_> = sorters::<ByKey>()
+            .into_iter()
+            .filter(|s| s.info().stable)
+            .collect();
+        assert!(stable.len() >= 7);
+        for s in stable {
+            let mut a: Vec<ByKey> = v.iter().map(|p| ByKey(*p)).collect();
+            s.sort(&mut a);
+            let a: Vec<(u32, usize)> = a.into_iter().map(|k| k.0).collect();
+            assert_eq!(a, sorted_v, "{}", s.info().name);
+        }
+    }
+    #[test]
+    fn test_sorter_lookup() {
+        // cargo test test_sorter_lookup -- --nocapture
+        let s = sorter::<String>("shell").unwrap();
+        assert_eq!(s.info().worst, Complexity::N32);
+        assert!(!s.info().stable);
+        let mut v: Vec<String> = vec!["b".into(), "c".into(), "a".into()];
+        s.sort(&mut v);
+        assert_eq!(v, vec!["a", "b", "c"]);
+        assert!(sorter::<u8>("nope").is_none());
+
+        let parallel: Vec<&str> = sorters::<u8>()
+            .iter()
+            .filter(|s| s.info().parallel)
+            .map(|s| s.info().name)
+            .collect();
+        assert_eq!(
+            parallel,
+            vec!["merge_rayon", "threaded", "rayon", "rayon_3way"]
+        );
+        assert_eq!(Complexity::NLogN.to_string(), "O(n ln(n))");
+    }
+}
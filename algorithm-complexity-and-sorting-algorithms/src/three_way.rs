@@ -0,0 +1,299 @@
+use std::cmp::Ordering;
+
+use crate::b_rand::{PivotRng, ThreadRng};
+use crate::observer::{NoOp, SortObserver};
+
+// Dutch national flag partition around a random pivot
+// output (lt, gt) where
+// v[..lt] is less than the pivot
+// v[lt..gt] is equal to the pivot
+// v[gt..] is greater than the pivot
+pub fn pivot_3way<T: PartialOrd>(v: &mut [T]) -> (usize, usize) {
+    pivot_3way_with_rng(v, &mut ThreadRng)
+}
+
+pub fn pivot_3way_with_rng<T: PartialOrd, R: PivotRng>(v: &mut [T], rng: &mut R) -> (usize, usize) {
+    pivot_3way_at(v, 0, rng, &mut |a: &T, b: &T| a < b, &NoOp)
+}
+
+pub fn pivot_3way_by<T, F>(v: &mut [T], mut compare: F) -> (usize, usize)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    pivot_3way_at(
+        v,
+        0,
+        &mut ThreadRng,
+        &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    )
+}
+
+pub(crate) fn pivot_3way_at<T, R, F, O>(
+    v: &mut [T],
+    off: usize,
+    rng: &mut R,
+    is_less: &mut F,
+    obs: &O,
+) -> (usize, usize)
+where
+    R: PivotRng,
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    if v.is_empty() {
+        return (0, 0);
+    }
+    let p = rng.pivot(v, is_less);
+    obs.swap(off + p, off);
+    v.swap(p, 0);
+
+    // v[lt] is always a copy of the pivot
+    let mut lt = 0;
+    let mut i = 1;
+    let mut gt = v.len();
+    while i < gt {
+        obs.compare(off + i, off + lt);
+        if is_less(&v[i], &v[lt]) {
+            obs.swap(off + lt, off + i);
+            v.swap(lt, i);
+            lt += 1;
+            i += 1;
+            continue;
+        }
+        obs.compare(off + lt, off + i);
+        if is_less(&v[lt], &v[i]) {
+            gt -= 1;
+            obs.swap(off + i, off + gt);
+            v.swap(i, gt);
+        } else {
+            i += 1;
+        }
+    }
+    obs.partition(off, off + v.len(), off + lt);
+    (lt, gt)
+}
+
+// quick sort that never recurses on keys equal to the pivot
+// few distinct keys costs O(n * keys) rather than O(n^2)
+pub fn quick_sort_3way<T: PartialOrd>(v: &mut [T]) {
+    quick_sort_3way_observed(v, &NoOp);
+}
+
+pub fn quick_sort_3way_observed<T: PartialOrd, O: SortObserver<T>>(v: &mut [T], obs: &O) {
+    quick_sort_3way_at(v, 0, 0, &mut ThreadRng, &mut |a: &T, b: &T| a < b, obs);
+}
+
+pub fn quick_sort_3way_with_rng<T: PartialOrd, R: PivotRng>(v: &mut [T], rng: &mut R) {
+    quick_sort_3way_at(v, 0, 0, rng, &mut |a: &T, b: &T| a < b, &NoOp);
+}
+
+pub fn quick_sort_3way_by<T, F>(v: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    quick_sort_3way_at(
+        v,
+        0,
+        0,
+        &mut ThreadRng,
+        &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    );
+}
+
+pub fn quick_sort_3way_by_key<T, K, F>(v: &mut [T], mut f: F)
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    quick_sort_3way_at(
+        v,
+        0,
+        0,
+        &mut ThreadRng,
+        &mut |a: &T, b: &T| f(a) < f(b),
+        &NoOp,
+    );
+}
+
+fn quick_sort_3way_at<T, R, F, O>(
+    v: &mut [T],
+    off: usize,
+    depth: usize,
+    rng: &mut R,
+    is_less: &mut F,
+    obs: &O,
+) where
+    R: PivotRng,
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    obs.depth(depth);
+    if v.len() <= 1 {
+        return;
+    }
+    let (lt, gt) = pivot_3way_at(v, off, rng, is_less, obs);
+    obs.pass(v);
+
+    let (a, b) = v.split_at_mut(lt);
+    quick_sort_3way_at(a, off, depth + 1, rng, is_less, obs);
+    quick_sort_3way_at(&mut b[gt - lt..], off + gt, depth + 1, rng, is_less, obs);
+}
+
+pub fn quick_sort_3way_rayon<T: Send + PartialOrd>(v: &mut [T]) {
+    quick_sort_3way_rayon_observed(v, &NoOp);
+}
+
+pub fn quick_sort_3way_rayon_observed<T, O>(v: &mut [T], obs: &O)
+where
+    T: Send + PartialOrd,
+    O: SortObserver<T> + Sync,
+{
+    quick_sort_3way_rayon_at(v, 0, 0, &mut ThreadRng, &|a: &T, b: &T| a < b, obs);
+}
+
+pub fn quick_sort_3way_rayon_with_rng<T, R>(v: &mut [T], rng: &mut R)
+where
+    T: Send + PartialOrd,
+    R: PivotRng + Clone + Send,
+{
+    quick_sort_3way_rayon_at(v, 0, 0, rng, &|a: &T, b: &T| a < b, &NoOp);
+}
+
+pub fn quick_sort_3way_rayon_by<T, F>(v: &mut [T], compare: F)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    quick_sort_3way_rayon_at(
+        v,
+        0,
+        0,
+        &mut ThreadRng,
+        &|a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    );
+}
+
+pub fn quick_sort_3way_rayon_by_key<T, K, F>(v: &mut [T], f: F)
+where
+    T: Send,
+    K: PartialOrd,
+    F: Fn(&T) -> K + Sync,
+{
+    quick_sort_3way_rayon_at(v, 0, 0, &mut ThreadRng, &|a: &T, b: &T| f(a) < f(b), &NoOp);
+}
+
+fn quick_sort_3way_rayon_at<T, R, F, O>(
+    v: &mut [T],
+    off: usize,
+    depth: usize,
+    rng: &mut R,
+    is_less: &F,
+    obs: &O,
+) where
+    T: Send,
+    R: PivotRng + Clone + Send,
+    F: Fn(&T, &T) -> bool + Sync,
+    O: SortObserver<T> + Sync,
+{
+    obs.depth(depth);
+    if v.len() <= 1 {
+        return;
+    }
+    let (lt, gt) = pivot_3way_at(v, off, rng, &mut |a: &T, b: &T| is_less(a, b), obs);
+    obs.pass(v);
+
+    let (a, b) = v.split_at_mut(lt);
+    let b = &mut b[gt - lt..];
+    let mut b_rng = rng.clone();
+    rayon::join(
+        || quick_sort_3way_rayon_at(a, off, depth + 1, rng, is_less, obs),
+        || quick_sort_3way_rayon_at(b, off + gt, depth + 1, &mut b_rng, is_less, obs),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observer::Counter;
+    use crate::quick_sort_observed;
+
+    #[test]
+    fn test_pivot_3way() {
+        // cargo test test_pivot_3way -- --nocapture
+        let mut v = vec![4, 6, 1, 4, 19, 8, 4, 11, 13, 3, 4];
+        let (lt, gt) = pivot_3way(&mut v);
+        assert!(lt < gt);
+
+        let p = v[lt];
+        for (x, val) in v.iter().enumerate() {
+            if x < lt {
+                assert!(*val < p);
+            } else if x < gt {
+                assert!(*val == p);
+            } else {
+                assert!(*val > p);
+            }
+        }
+    }
+    #[test]
+    fn test_quick_sort_3way() {
+        // cargo test test_quick_sort_3way -- --nocapture
+        let mut v = vec![4, 6, 1, 8, 11, 13, 3];
+        quick_sort_3way(&mut v);
+
+        let sorted_v = vec![1, 3, 4, 6, 8, 11, 13];
+        assert_eq!(v, sorted_v);
+    }
+    #[test]
+    fn test_quick_sort_3way_rayon() {
+        // cargo test test_quick_sort_3way_rayon -- --nocapture
+        let mut v: Vec<u32> = (0..10_000).map(|x| (x * 7919) % 13).collect();
+        let mut sorted_v = v.clone();
+        sorted_v.sort();
+
+        quick_sort_3way_rayon(&mut v);
+        assert_eq!(v, sorted_v);
+    }
+    #[test]
+    fn test_quick_sort_3way_by() {
+        // cargo test test_quick_sort_3way_by -- --nocapture
+        let mut v: Vec<(u8, usize)> = (0..200).map(|x| ((x % 5) as u8, x)).collect();
+        quick_sort_3way_by_key(&mut v, |p| p.0);
+        assert!(v.windows(2).all(|w| w[0].0 <= w[1].0));
+
+        quick_sort_3way_rayon_by(&mut v, |a, b| b.0.cmp(&a.0));
+        assert!(v.windows(2).all(|w| w[0].0 >= w[1].0));
+
+        let mut v = vec![4, 6, 1, 8, 11, 13, 3];
+        quick_sort_3way_by(&mut v, |a, b| b.cmp(a));
+        assert_eq!(v, vec![13, 11, 8, 6, 4, 3, 1]);
+
+        quick_sort_3way_rayon_by_key(&mut v, |x| *x);
+        assert_eq!(v, vec![1, 3, 4, 6, 8, 11, 13]);
+    }
+    #[test]
+    fn test_3way_few_keys() {
+        // cargo test test_3way_few_keys -- --nocapture
+        // 4 status codes, each key is partitioned out once
+        let codes = [200, 404, 500, 301];
+        let v: Vec<u32> = (0..2_000).map(|x| codes[(x * 31) % 4]).collect();
+
+        let c3 = Counter::new();
+        let mut v3 = v.clone();
+        quick_sort_3way_observed(&mut v3, &c3);
+
+        let c2 = Counter::new();
+        let mut v2 = v.clone();
+        quick_sort_observed(&mut v2, &c2);
+
+        assert_eq!(v3, v2);
+        let (t3, t2) = (c3.totals(), c2.totals());
+        println!("3way {}\n2way {}", t3, t2);
+        assert!(t3.partitions <= codes.len());
+        assert!(t3.comparisons <= 2 * v.len() * codes.len());
+        assert!(t3.comparisons < t2.comparisons);
+    }
+}
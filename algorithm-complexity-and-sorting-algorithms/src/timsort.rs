@@ -0,0 +1,584 @@
+use std::cmp::Ordering;
+use std::ptr;
+use std::slice;
+
+use crate::merge::Hole;
+use crate::observer::{NoOp, SortObserver};
+
+// once one side wins this many times in a row
+// stop comparing one at a time and gallop instead
+const MIN_GALLOP: usize = 7;
+
+// Natural merge sort in the style of Timsort
+// finds the runs already in the data, ascending or strictly descending,
+// tops short runs up with binary insertion sort then merges runs
+// stable, O(n) on sorted input, O(n ln(n)) worst case
+pub fn tim_sort<T: PartialOrd>(v: &mut [T]) {
+    tim_sort_observed(v, &NoOp);
+}
+
+pub fn tim_sort_observed<T: PartialOrd, O: SortObserver<T>>(v: &mut [T], obs: &O) {
+    tim_sort_impl(v, &mut |a: &T, b: &T| a < b, obs);
+}
+
+pub fn tim_sort_by<T, F>(v: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    tim_sort_impl(
+        v,
+        &mut |a: &T, b: &T| compare(a, b) == Ordering::Less,
+        &NoOp,
+    );
+}
+
+pub fn tim_sort_by_key<T, K, F>(v: &mut [T], mut f: F)
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    tim_sort_impl(v, &mut |a: &T, b: &T| f(a) < f(b), &NoOp);
+}
+
+fn tim_sort_impl<T, F, O>(v: &mut [T], is_less: &mut F, obs: &O)
+where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    let n = v.len();
+    if n < 2 || std::mem::size_of::<T>() == 0 {
+        return;
+    }
+    let min_run = min_run(n);
+    // every merge copies out the shorter run, which is never over n/2
+    let mut scratch: Vec<T> = Vec::with_capacity(n / 2);
+    let buf = scratch.as_mut_ptr();
+
+    // (start, len) of each run not yet merged
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let mut lo = 0;
+    while lo < n {
+        let mut run = count_run(&mut v[lo..], lo, is_less, obs);
+        if run < min_run {
+            let end = std::cmp::min(lo + min_run, n);
+            binary_insertion_sort(&mut v[lo..end], run, lo, is_less, obs);
+            run = end - lo;
+        }
+        runs.push((lo, run));
+        lo += run;
+        collapse(v, &mut runs, buf, is_less, obs);
+    }
+    while runs.len() > 1 {
+        let i = runs.len() - 2;
+        merge_at(v, &mut runs, i, buf, is_less, obs);
+    }
+}
+
+// n if n is small, else between 32 and 64
+// chosen so n / min_run is a power of 2 or just under one
+pub(crate) fn min_run(mut n: usize) -> usize {
+    let mut r = 0;
+    while n >= 64 {
+        r |= n & 1;
+        n >>= 1;
+    }
+    n + r
+}
+
+// length of the run at the start of v
+// a strictly descending run is reversed so it ascends,
+// strictly so that equal elements never swap order
+fn count_run<T, F, O>(v: &mut [T], off: usize, is_less: &mut F, obs: &O) -> usize
+where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    if v.len() < 2 {
+        return v.len();
+    }
+    let mut end = 2;
+    obs.compare(off + 1, off);
+    if is_less(&v[1], &v[0]) {
+        while end < v.len() {
+            obs.compare(off + end, off + end - 1);
+            if !is_less(&v[end], &v[end - 1]) {
+                break;
+            }
+            end += 1;
+        }
+        v[..end].reverse();
+    } else {
+        while end < v.len() {
+            obs.compare(off + end, off + end - 1);
+            if is_less(&v[end], &v[end - 1]) {
+                break;
+            }
+            end += 1;
+        }
+    }
+    end
+}
+
+// v[..start] is sorted, binary search a home for each element after it
+fn binary_insertion_sort<T, F, O>(v: &mut [T], start: usize, off: usize, is_less: &mut F, obs: &O)
+where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    for i in std::cmp::max(start, 1)..v.len() {
+        let (sorted, rest) = v.split_at(i);
+        let x = &rest[0];
+        // after any equal elements, keeps it stable
+        let (mut lo, mut hi) = (0, i);
+        while lo < hi {
+            let m = lo + (hi - lo) / 2;
+            obs.compare(off + i, off + m);
+            if is_less(x, &sorted[m]) {
+                hi = m;
+            } else {
+                lo = m + 1;
+            }
+        }
+        v[lo..=i].rotate_right(1);
+    }
+}
+
+// Keep the run lengths on the stack growing like fibonacci numbers
+// so merges stay balanced and the stack stays O(ln(n)).
+// Checks the top four runs, checking three is not enough
+fn collapse<T, F, O>(
+    v: &mut [T],
+    runs: &mut Vec<(usize, usize)>,
+    buf: *mut T,
+    is_less: &mut F,
+    obs: &O,
+) where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    loop {
+        let n = runs.len();
+        if n < 2 {
+            return;
+        }
+        let len = |i: usize| runs[i].1;
+        let i = if (n >= 3 && len(n - 3) <= len(n - 2) + len(n - 1))
+            || (n >= 4 && len(n - 4) <= len(n - 3) + len(n - 2))
+        {
+            if n >= 3 && len(n - 3) < len(n - 1) {
+                n - 3
+            } else {
+                n - 2
+            }
+        } else if len(n - 2) <= len(n - 1) {
+            n - 2
+        } else {
+            return;
+        };
+        merge_at(v, runs, i, buf, is_less, obs);
+    }
+}
+
+// merge runs i and i + 1 on the stack
+fn merge_at<T, F, O>(
+    v: &mut [T],
+    runs: &mut Vec<(usize, usize)>,
+    i: usize,
+    buf: *mut T,
+    is_less: &mut F,
+    obs: &O,
+) where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    let (s1, l1) = runs[i];
+    let (s2, l2) = runs[i + 1];
+    runs[i] = (s1, l1 + l2);
+    runs.remove(i + 1);
+
+    let run = &mut v[s1..s2 + l2];
+    // the front of a that is not above b[0] is already in place
+    let skip = gallop(
+        &run[..l1],
+        &mut |j, a| {
+            obs.compare(s2, s1 + j);
+            !is_less(&run[l1], a)
+        },
+        false,
+    );
+    if skip == l1 {
+        return;
+    }
+    // the back of b that is not below the last of a is also in place
+    let keep = gallop(
+        &run[l1..],
+        &mut |j, b| {
+            obs.compare(s2 + j, s2 - 1);
+            is_less(b, &run[l1 - 1])
+        },
+        true,
+    );
+    let run = &mut run[skip..l1 + keep];
+    let mid = l1 - skip;
+    // buf holds n/2 and the shorter run is never more than that
+    unsafe {
+        if mid <= run.len() - mid {
+            gallop_merge_lo(run, mid, s1 + skip, buf, is_less, obs);
+        } else {
+            gallop_merge_hi(run, mid, s1 + skip, buf, is_less, obs);
+        }
+    }
+    obs.pass(v);
+}
+
+// run is true for a prefix and false after it, return the prefix length.
+// galloping probes 1, 2, 4, 8 .. from one end before a binary search
+// so the cost is O(ln(k)) where k is the distance from that end
+pub(crate) fn gallop<T, P>(run: &[T], pred: &mut P, from_end: bool) -> usize
+where
+    P: FnMut(usize, &T) -> bool,
+{
+    let n = run.len();
+    let (mut lo, mut hi) = (0, n);
+    if !from_end {
+        let mut i = 0;
+        while i < n {
+            if !pred(i, &run[i]) {
+                hi = i;
+                break;
+            }
+            lo = i + 1;
+            i = 2 * i + 1;
+        }
+    } else {
+        let mut d = 1;
+        while d <= n {
+            let i = n - d;
+            if pred(i, &run[i]) {
+                lo = i + 1;
+                break;
+            }
+            hi = i;
+            d *= 2;
+        }
+    }
+    while lo < hi {
+        let m = lo + (hi - lo) / 2;
+        if pred(m, &run[m]) {
+            lo = m + 1;
+        } else {
+            hi = m;
+        }
+    }
+    lo
+}
+
+// left run is shorter, copy it out and merge forwards
+unsafe fn gallop_merge_lo<T, F, O>(
+    v: &mut [T],
+    mid: usize,
+    off: usize,
+    buf: *mut T,
+    is_less: &mut F,
+    obs: &O,
+) where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    let len = v.len();
+    let v = v.as_mut_ptr();
+    ptr::copy_nonoverlapping(v, buf, mid);
+    let mut hole = Hole {
+        start: buf,
+        end: buf.add(mid),
+        dest: v,
+    };
+    let mut right = v.add(mid);
+    let v_end = v.add(len);
+    let (mut a_wins, mut b_wins) = (0, 0);
+
+    while hole.start < hole.end && right < v_end {
+        if a_wins < MIN_GALLOP && b_wins < MIN_GALLOP {
+            obs.compare(
+                off + right.offset_from(v) as usize,
+                off + hole.start.offset_from(buf) as usize,
+            );
+            if is_less(&*right, &*hole.start) {
+                ptr::copy_nonoverlapping(right, hole.dest, 1);
+                right = right.add(1);
+                b_wins += 1;
+                a_wins = 0;
+            } else {
+                ptr::copy_nonoverlapping(hole.start, hole.dest, 1);
+                hole.start = hole.start.add(1);
+                a_wins += 1;
+                b_wins = 0;
+            }
+            hole.dest = hole.dest.add(1);
+            continue;
+        }
+
+        // how much of a goes before the head of b
+        let a_rem = slice::from_raw_parts(hole.start, hole.end.offset_from(hole.start) as usize);
+        let b_head = &*right;
+        let b_at = off + right.offset_from(v) as usize;
+        let ka = gallop(
+            a_rem,
+            &mut |j, a| {
+                obs.compare(b_at, off + j);
+                !is_less(b_head, a)
+            },
+            false,
+        );
+        ptr::copy_nonoverlapping(hole.start, hole.dest, ka);
+        hole.start = hole.start.add(ka);
+        hole.dest = hole.dest.add(ka);
+        if hole.start == hole.end {
+            break;
+        }
+
+        // how much of b goes before the head of a
+        let b_rem = slice::from_raw_parts(right, v_end.offset_from(right) as usize);
+        let a_head = &*hole.start;
+        let kb = gallop(
+            b_rem,
+            &mut |j, b| {
+                obs.compare(b_at + j, off);
+                is_less(b, a_head)
+            },
+            false,
+        );
+        // the gap is only as big as the rest of a, so these can overlap
+        ptr::copy(right, hole.dest, kb);
+        right = right.add(kb);
+        hole.dest = hole.dest.add(kb);
+
+        // galloping did not pay, go back to one at a time
+        if ka < MIN_GALLOP && kb < MIN_GALLOP {
+            a_wins = 0;
+            b_wins = 0;
+        }
+    }
+    // dropping hole moves what is left of the left run into place
+}
+
+// right run is shorter, copy it out and merge backwards
+unsafe fn gallop_merge_hi<T, F, O>(
+    v: &mut [T],
+    mid: usize,
+    off: usize,
+    buf: *mut T,
+    is_less: &mut F,
+    obs: &O,
+) where
+    F: FnMut(&T, &T) -> bool,
+    O: SortObserver<T>,
+{
+    let len = v.len();
+    let v = v.as_mut_ptr();
+    ptr::copy_nonoverlapping(v.add(mid), buf, len - mid);
+    let mut hole = Hole {
+        start: buf,
+        end: buf.add(len - mid),
+        dest: v.add(mid),
+    };
+    let mut out = v.add(len);
+    let (mut a_wins, mut b_wins) = (0, 0);
+
+    while v < hole.dest && hole.start < hole.end {
+        if a_wins < MIN_GALLOP && b_wins < MIN_GALLOP {
+            let l = hole.dest.sub(1);
+            let r = hole.end.sub(1);
+            obs.compare(
+                off + mid + r.offset_from(buf) as usize,
+                off + l.offset_from(v) as usize,
+            );
+            out = out.sub(1);
+            if is_less(&*r, &*l) {
+                ptr::copy_nonoverlapping(l, out, 1);
+                hole.dest = l;
+                a_wins += 1;
+                b_wins = 0;
+            } else {
+                ptr::copy_nonoverlapping(r, out, 1);
+                hole.end = r;
+                b_wins += 1;
+                a_wins = 0;
+            }
+            continue;
+        }
+
+        // how much of the back of a goes after the last of b
+        let a_rem = slice::from_raw_parts(v, hole.dest.offset_from(v) as usize);
+        let b_last = &*hole.end.sub(1);
+        let b_at = off + mid + hole.end.offset_from(buf) as usize - 1;
+        let a_keep = gallop(
+            a_rem,
+            &mut |j, a| {
+                obs.compare(b_at, off + j);
+                !is_less(b_last, a)
+            },
+            true,
+        );
+        let ka = a_rem.len() - a_keep;
+        out = out.sub(ka);
+        // the gap is only as big as the rest of b, so these can overlap
+        ptr::copy(v.add(a_keep), out, ka);
+        hole.dest = v.add(a_keep);
+        if hole.dest == v {
+            break;
+        }
+
+        // how much of the back of b goes after the last of a
+        let b_rem = slice::from_raw_parts(hole.start, hole.end.offset_from(hole.start) as usize);
+        let a_last = &*hole.dest.sub(1);
+        let b_keep = gallop(
+            b_rem,
+            &mut |j, b| {
+                obs.compare(off + mid + j, off + a_keep - 1);
+                is_less(b, a_last)
+            },
+            true,
+        );
+        let kb = b_rem.len() - b_keep;
+        out = out.sub(kb);
+        ptr::copy_nonoverlapping(hole.start.add(b_keep), out, kb);
+        hole.end = hole.start.add(b_keep);
+
+        // galloping did not pay, go back to one at a time
+        if ka < MIN_GALLOP && kb < MIN_GALLOP {
+            a_wins = 0;
+            b_wins = 0;
+        }
+    }
+    // dropping hole moves what is left of the right run into place
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::b_rand::RandGen;
+    use crate::observer::Counter;
+
+    fn random_vec(seed: usize, n: usize, max: usize) -> Vec<usize> {
+        let mut r = RandGen::new(seed);
+        (0..n).map(|_| r.next_v(max)).collect()
+    }
+
+    #[test]
+    fn test_tim_sort() {
+        // cargo test test_tim_sort -- --nocapture
+        let mut v = vec![4, 6, 1, 8, 11, 13, 3];
+        tim_sort(&mut v);
+
+        let sorted_v = vec![1, 3, 4, 6, 8, 11, 13];
+        assert_eq!(v, sorted_v);
+    }
+    #[test]
+    fn test_min_run() {
+        // cargo test test_min_run -- --nocapture
+        assert_eq!(min_run(10), 10);
+        assert_eq!(min_run(64), 32);
+        assert_eq!(min_run(65), 33);
+        for n in 64..5000 {
+            let r = min_run(n);
+            assert!((32..=64).contains(&r));
+        }
+    }
+    #[test]
+    fn test_gallop() {
+        // cargo test test_gallop -- --nocapture
+        let v: Vec<u32> = (0..100).collect();
+        for k in 0..=100 {
+            assert_eq!(gallop(&v, &mut |_, x| *x < k, false), k as usize);
+            assert_eq!(gallop(&v, &mut |_, x| *x < k, true), k as usize);
+        }
+    }
+    #[test]
+    fn test_tim_sort_random() {
+        // cargo test test_tim_sort_random -- --nocapture
+        for n in (0..2000).step_by(37) {
+            let mut v = random_vec(n + 3, n, 1 + n / 4);
+            let mut sorted_v = v.clone();
+            sorted_v.sort();
+            tim_sort(&mut v);
+            assert_eq!(v, sorted_v);
+        }
+    }
+    #[test]
+    fn test_tim_sort_stable() {
+        // cargo test test_tim_sort_stable -- --nocapture
+        // runs going up and down with lots of equal keys
+        let keys: Vec<usize> = (0..3000).map(|x| (x / 50) % 7 + (x % 3)).collect();
+        let tagged: Vec<(usize, usize)> =
+            keys.into_iter().enumerate().map(|(i, k)| (k, i)).collect();
+
+        let mut a = tagged.clone();
+        tim_sort_by_key(&mut a, |p| p.0);
+
+        let mut sorted_v = tagged;
+        sorted_v.sort();
+        assert_eq!(a, sorted_v);
+    }
+    #[test]
+    fn test_tim_sort_runs_are_linear() {
+        // cargo test test_tim_sort_runs_are_linear -- --nocapture
+        let n = 10_000;
+        for v in [(0..n).collect::<Vec<_>>(), (0..n).rev().collect()].iter_mut() {
+            let c = Counter::new();
+            tim_sort_observed(v, &c);
+            assert_eq!(*v, (0..n).collect::<Vec<_>>());
+            assert_eq!(c.totals().comparisons, n - 1);
+        }
+    }
+    #[test]
+    fn test_tim_sort_gallops() {
+        // cargo test test_tim_sort_gallops -- --nocapture
+        // two runs made of alternating blocks of 100,
+        // galloping copies whole blocks after a few comparisons
+        let n = 10_000;
+        let a = (0..n).filter(|x| (x / 100) % 2 == 0);
+        let b = (0..n).filter(|x| (x / 100) % 2 == 1);
+        let mut v: Vec<usize> = a.chain(b).collect();
+
+        let c = Counter::new();
+        tim_sort_observed(&mut v, &c);
+        assert_eq!(v, (0..n).collect::<Vec<_>>());
+        println!("{}", c.totals());
+        // n - 1 to find the 2 runs, one at a time merging would be n more
+        assert!(c.totals().comparisons < n + n / 3);
+
+        let mut v: Vec<usize> = (4_000..9_000).chain(0..5_000).collect();
+        let mut sorted_v = v.clone();
+        sorted_v.sort();
+        tim_sort_by(&mut v, |a, b| a.cmp(b));
+        assert_eq!(v, sorted_v);
+    }
+    #[test]
+    fn test_tim_sort_panic_safe() {
+        // cargo test test_tim_sort_panic_safe -- --nocapture
+        let v: Vec<String> = random_vec(9, 500, 100)
+            .into_iter()
+            .map(|x| x.to_string())
+            .collect();
+        for stop in &[50, 1000, 3000] {
+            let mut a = v.clone();
+            let mut n = 0;
+            let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                tim_sort_by(&mut a, |x, y| {
+                    n += 1;
+                    if n == *stop {
+                        panic!("stop");
+                    }
+                    x.cmp(y)
+                });
+            }));
+            assert!(res.is_err());
+
+            a.sort();
+            let mut v_sorted = v.clone();
+            v_sorted.sort();
+            assert_eq!(a, v_sorted);
+        }
+    }
+}
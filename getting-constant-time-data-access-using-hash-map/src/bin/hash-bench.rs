@@ -0,0 +1,104 @@
+// Times MyHash against MyHashLegacy, and std's SipHash for scale,
+// over keys of a few lengths, one CSV row per hasher and length.
+//
+// cargo run --release --bin hash-bench -- --lens 8,64,4096 --mb 16
+//
+// columns: hasher,key_len,bytes,micros,mb_per_sec
+
+use getting_constant_time_data_access_using_hash_map::{MyHash, MyHashLegacy};
+use rand::{RngCore, SeedableRng};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::hint::black_box;
+use std::io::{self, BufWriter, Write};
+use std::time::Instant;
+
+struct Args {
+    lens: Vec<usize>,
+    mb: usize,
+}
+
+fn parse_list<T: std::str::FromStr>(flag: &str, s: Option<String>) -> Vec<T> {
+    let s = s.unwrap_or_else(|| usage(&format!("{} needs a value", flag)));
+    s.split(',')
+        .map(|x| {
+            x.trim()
+                .parse()
+                .unwrap_or_else(|_| usage(&format!("bad value {:?} for {}", x, flag)))
+        })
+        .collect()
+}
+
+fn usage(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    eprintln!("usage: hash-bench [--lens 8,64,4096] [--mb 16]");
+    std::process::exit(2);
+}
+
+fn parse_args() -> Args {
+    let mut a = Args {
+        lens: vec![4, 8, 16, 64, 256, 4096],
+        mb: 16,
+    };
+    let mut it = std::env::args().skip(1);
+    while let Some(flag) = it.next() {
+        match flag.as_str() {
+            "--lens" => a.lens = parse_list(&flag, it.next()),
+            "--mb" => match parse_list(&flag, it.next())[..] {
+                [mb] => a.mb = mb,
+                _ => usage("--mb takes one value"),
+            },
+            "-h" | "--help" => usage("hash-bench, bytes per second for each hasher"),
+            _ => usage(&format!("unknown argument {}", flag)),
+        }
+    }
+    a
+}
+
+// hashes every key with a fresh hasher from new, returns micros.
+// the best of a few runs, so the first one warming up doesn't count
+fn time<H: Hasher, F: Fn() -> H>(keys: &[Vec<u8>], new: F) -> u128 {
+    (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            for k in keys {
+                let mut h = new();
+                h.write(black_box(k));
+                black_box(h.finish());
+            }
+            start.elapsed().as_micros()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() -> io::Result<()> {
+    let args = parse_args();
+    let mut out = BufWriter::new(io::stdout());
+    let mut r = rand::rngs::StdRng::seed_from_u64(1);
+    let sip = RandomState::new();
+
+    writeln!(out, "hasher,key_len,bytes,micros,mb_per_sec")?;
+    for &len in &args.lens {
+        // about --mb of keys, and at least one
+        let n = (args.mb << 20) / len.max(1) + 1;
+        let keys: Vec<Vec<u8>> = (0..n)
+            .map(|_| {
+                let mut k = vec![0u8; len];
+                r.fill_bytes(&mut k);
+                k
+            })
+            .collect();
+        let bytes = n * len;
+        let runs: [(&str, u128); 3] = [
+            ("my_hash", time(&keys, || MyHash::with_seed(7))),
+            ("my_hash_legacy", time(&keys, || MyHashLegacy::with_seed(7))),
+            ("sip", time(&keys, || sip.build_hasher())),
+        ];
+        for (name, micros) in runs.iter() {
+            let mbs = bytes as f64 / (1 << 20) as f64 / (*micros.max(&1) as f64 / 1e6);
+            writeln!(out, "{},{},{},{},{:.1}", name, len, bytes, micros, mbs)?;
+        }
+    }
+    out.flush()
+}
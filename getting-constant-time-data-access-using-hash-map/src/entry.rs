@@ -0,0 +1,275 @@
+use crate::{BucketList, HMap, MyHashBuilder, MAX_LOAD};
+use std::hash::{BuildHasher, Hash};
+
+// A key's place in the map, found with one hash and one search
+// of each bucket list, then read or filled without looking again.
+// S is only there for the map's type, the hashing is done by then
+
+pub enum Entry<'a, K, V, S = MyHashBuilder> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+pub struct OccupiedEntry<'a, K, V, S = MyHashBuilder> {
+    map: &'a mut HMap<K, V, S>,
+    // which list it is in, the bucket and the position in the bucket
+    in_grow: bool,
+    bucket: usize,
+    pos: usize,
+}
+
+pub struct VacantEntry<'a, K, V, S = MyHashBuilder> {
+    map: &'a mut HMap<K, V, S>,
+    key: K,
+    // kept so inserting doesn't hash again
+    hash: u64,
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HMap<K, V, S> {
+    pub fn entry(&mut self, k: K) -> Entry<'_, K, V, S> {
+        let hash = self.hash(&k);
+        let found = match self.main.find(hash, &k) {
+            Some(at) => Some((false, at)),
+            None => self.grow.find(hash, &k).map(|at| (true, at)),
+        };
+        match found {
+            Some((in_grow, (bucket, pos))) => Entry::Occupied(OccupiedEntry {
+                map: self,
+                in_grow,
+                bucket,
+                pos,
+            }),
+            None => Entry::Vacant(VacantEntry {
+                map: self,
+                key: k,
+                hash,
+            }),
+        }
+    }
+}
+
+impl<K, V, S> HMap<K, V, S> {
+    fn list(&self, in_grow: bool) -> &BucketList<K, V> {
+        if in_grow {
+            &self.grow
+        } else {
+            &self.main
+        }
+    }
+
+    fn list_mut(&mut self, in_grow: bool) -> &mut BucketList<K, V> {
+        if in_grow {
+            &mut self.grow
+        } else {
+            &mut self.main
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher> Entry<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+
+    pub fn or_insert(self, v: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(v),
+        }
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, f: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(f()),
+        }
+    }
+
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, f: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                let v = f(e.key());
+                e.insert(v)
+            }
+        }
+    }
+
+    // runs f on the value if there is one, for chaining into an or_insert
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(e) = &mut self {
+            f(e.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K: Hash + Eq, V: Default, S: BuildHasher> Entry<'a, K, V, S> {
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<'a, K, V, S> OccupiedEntry<'a, K, V, S> {
+    fn pair(&self) -> &(K, V) {
+        &self.map.list(self.in_grow).buckets[self.bucket][self.pos]
+    }
+
+    pub fn key(&self) -> &K {
+        &self.pair().0
+    }
+
+    pub fn get(&self) -> &V {
+        &self.pair().1
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.list_mut(self.in_grow).buckets[self.bucket][self.pos].1
+    }
+
+    // the value, borrowed for as long as the map was
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.list_mut(self.in_grow).buckets[self.bucket][self.pos].1
+    }
+
+    // swaps in v and gives back the old value, the key stays
+    pub fn insert(&mut self, v: V) -> V {
+        std::mem::replace(self.get_mut(), v)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        let list = self.map.list_mut(self.in_grow);
+        list.len -= 1;
+        list.buckets[self.bucket].swap_remove(self.pos)
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher> VacantEntry<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    // Once main would hold more than MAX_LOAD a bucket a move starts,
+    // and every new key moves one more bucket along, so the move is done
+    // before main could fill up again. Moving never shifts an entry
+    // already in grow, it only appends, so the place the new entry went stays good
+    pub fn insert(self, v: V) -> &'a mut V {
+        let map = self.map;
+        let (in_grow, (bucket, pos)) = if map.n_moved > 0 {
+            let at = map.grow.push(self.hash, self.key, v);
+            map.move_bucket();
+            // that may have been the last bucket, then grow became main
+            (map.n_moved > 0, at)
+        } else if map.main.len >= map.main.buckets.len() * MAX_LOAD {
+            // this one would overfill it, start the move first
+            // so the new entry goes straight to grow
+            map.move_bucket();
+            (true, map.grow.push(self.hash, self.key, v))
+        } else {
+            (false, map.main.push(self.hash, self.key, v))
+        };
+        &mut map.list_mut(in_grow).buckets[bucket][pos].1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_counting() {
+        // cargo test test_entry_counting -- --nocapture
+        let text = "the cat sat on the mat the end";
+        let mut hm = HMap::new();
+        for w in text.split(' ') {
+            *hm.entry(w).or_insert(0) += 1;
+        }
+        assert_eq!(hm.get("the"), Some(&3));
+        assert_eq!(hm.get("cat"), Some(&1));
+        assert_eq!(hm.len(), 6);
+
+        let mut lens: HMap<usize, Vec<&str>> = HMap::new();
+        for w in text.split(' ') {
+            lens.entry(w.len()).or_default().push(w);
+        }
+        assert_eq!(lens.get(&3).map(|v| v.len()), Some(7));
+        assert_eq!(lens.get(&2), Some(&vec!["on"]));
+    }
+
+    #[test]
+    fn test_entry_and_modify() {
+        // cargo test test_entry_and_modify -- --nocapture
+        let mut hm = HMap::new();
+        hm.entry("a").and_modify(|v| *v += 1).or_insert(10);
+        hm.entry("a").and_modify(|v| *v += 1).or_insert(10);
+        assert_eq!(hm.get("a"), Some(&11));
+
+        let v = hm.entry("b").or_insert_with(|| 5);
+        *v *= 2;
+        assert_eq!(hm.get("b"), Some(&10));
+        hm.entry("cc").or_insert_with_key(|k| k.len());
+        assert_eq!(hm.get("cc"), Some(&2));
+        assert_eq!(hm.entry("zz").key(), &"zz");
+    }
+
+    #[test]
+    fn test_occupied_entry() {
+        // cargo test test_occupied_entry -- --nocapture
+        let mut hm = HMap::new();
+        hm.insert("k".to_string(), 1);
+        match hm.entry("k".to_string()) {
+            Entry::Occupied(mut e) => {
+                assert_eq!(e.key(), "k");
+                assert_eq!(e.get(), &1);
+                assert_eq!(e.insert(2), 1);
+                assert_eq!(e.remove_entry(), ("k".to_string(), 2));
+            }
+            Entry::Vacant(_) => panic!("k is there"),
+        }
+        assert!(hm.is_empty());
+        match hm.entry("k".to_string()) {
+            Entry::Vacant(e) => assert_eq!(e.into_key(), "k"),
+            Entry::Occupied(_) => panic!("k was removed"),
+        }
+        assert!(hm.is_empty());
+    }
+
+    #[test]
+    fn test_entry_keeps_growing() {
+        // cargo test test_entry_keeps_growing -- --nocapture
+        // every key through entry, so every move happens in VacantEntry::insert
+        let mut hm = HMap::new();
+        for x in 0..10_000u32 {
+            let v = hm.entry(x).or_insert(x + 1);
+            // the reference has to be to the new entry wherever it ended up
+            assert_eq!(*v, x + 1);
+            *v += 1;
+        }
+        assert_eq!(hm.len(), 10_000);
+        for x in 0..10_000u32 {
+            assert_eq!(hm.get(&x), Some(&(x + 2)), "{}", x);
+        }
+        for b in hm.main.buckets.iter().chain(hm.grow.buckets.iter()) {
+            assert!(b.len() < 10, "bucket too big {}", b.len());
+        }
+        for x in (0..10_000u32).step_by(2) {
+            match hm.entry(x) {
+                Entry::Occupied(e) => assert_eq!(e.remove(), x + 2),
+                Entry::Vacant(_) => panic!("{} missing", x),
+            }
+        }
+        assert_eq!(hm.len(), 5_000);
+        assert_eq!(hm.iter().count(), 5_000);
+    }
+}
@@ -1,15 +1,117 @@
-use std::hash::{Hash, Hasher};
+use std::convert::TryInto;
+use std::hash::{BuildHasher, Hash, Hasher};
 
+// Murmur3's 64 bit constants, for mixing in each word
+const K1: u64 = 0x87c3_7b91_1142_53d5;
+const K2: u64 = 0x4cf5_ad43_2745_937f;
+
+// Takes the input 8 bytes at a time, each word mixed in with a
+// multiply and rotate, then finish runs the lot through Murmur3's
+// finalizer so every input bit moves about half the output bits.
+// Not the same values as MyHashLegacy, see that for old hashes
+#[derive(Debug, Clone)]
 pub struct MyHash {
+    h: u64,
+    // bytes written, so inputs that pad out the same still differ
+    len: u64,
+}
+
+impl MyHash {
+    pub fn with_seed(seed: u64) -> Self {
+        MyHash { h: seed, len: 0 }
+    }
+
+    #[inline]
+    fn word(&mut self, w: u64) {
+        let w = w.wrapping_mul(K1).rotate_left(31).wrapping_mul(K2);
+        self.h = (self.h ^ w)
+            .rotate_left(27)
+            .wrapping_mul(5)
+            .wrapping_add(0x52dc_e729);
+    }
+
+    #[inline]
+    fn int(&mut self, n: u64, size: u64) {
+        self.len += size;
+        self.word(n);
+    }
+}
+
+impl Hasher for MyHash {
+    fn write(&mut self, dt: &[u8]) {
+        self.len += dt.len() as u64;
+        let mut words = dt.chunks_exact(8);
+        for w in &mut words {
+            self.word(u64::from_le_bytes(w.try_into().unwrap()));
+        }
+        let rest = words.remainder();
+        if !rest.is_empty() {
+            // at most 7 bytes, the top one says how many
+            let mut b = [0u8; 8];
+            b[..rest.len()].copy_from_slice(rest);
+            b[7] = rest.len() as u8;
+            self.word(u64::from_le_bytes(b));
+        }
+    }
+
+    // ints are one word each, no need to go through bytes
+    fn write_u8(&mut self, n: u8) {
+        self.int(n as u64, 1);
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        self.int(n as u64, 2);
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.int(n as u64, 4);
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.int(n, 8);
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.int(n as u64, std::mem::size_of::<usize>() as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        fmix(self.h ^ self.len)
+    }
+}
+
+// Murmur3's fmix64
+fn fmix(mut k: u64) -> u64 {
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    k ^= k >> 33;
+    k
+}
+
+// The first MyHash, a u128 multiply and modulo for every byte.
+// Slow, but kept so hashes already saved somewhere (the blob
+// store's bucket numbers) still come out the same
+#[derive(Debug, Clone)]
+pub struct MyHashLegacy {
     prev: u8,
     number: u128,
 }
 
-impl Hasher for MyHash {
+impl MyHashLegacy {
+    pub fn with_seed(seed: u64) -> Self {
+        let mut h = MyHashLegacy { number: 0, prev: 0 };
+        h.write_u64(seed);
+        h
+    }
+}
+
+impl Hasher for MyHashLegacy {
     fn write(&mut self, dt: &[u8]) {
         for d in dt {
             self.number = ((self.number + 11) * (*d as u128 + 13) + ((d ^ self.prev) as u128))
-                % (std::u64::MAX as u128);
+                % (u64::MAX as u128);
             self.prev = *d;
         }
     }
@@ -19,9 +121,45 @@ impl Hasher for MyHash {
     }
 }
 
+// Makes a MyHash with the seed already in, the default
+// hasher for HMap. Two builders with the same seed hash the same
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MyHashBuilder {
+    seed: u64,
+}
+
+impl MyHashBuilder {
+    // a random seed, so no one can pick keys that all land in one bucket
+    pub fn new() -> Self {
+        MyHashBuilder::with_seed(rand::random())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        MyHashBuilder { seed }
+    }
+}
+
+impl Default for MyHashBuilder {
+    fn default() -> Self {
+        MyHashBuilder::new()
+    }
+}
+
+impl BuildHasher for MyHashBuilder {
+    type Hasher = MyHash;
+
+    fn build_hasher(&self) -> MyHash {
+        MyHash::with_seed(self.seed)
+    }
+}
+
 pub fn hash<T: Hash>(seed: u64, t: T) -> u64 {
-    let mut h = MyHash { number: 0, prev: 0 };
-    h.write_u64(seed);
+    MyHashBuilder::with_seed(seed).hash_one(t)
+}
+
+// what hash gave before MyHash was rewritten
+pub fn hash_legacy<T: Hash>(seed: u64, t: T) -> u64 {
+    let mut h = MyHashLegacy::with_seed(seed);
     t.hash(&mut h);
     h.finish()
 }
@@ -46,4 +184,83 @@ mod test {
             prev = curr;
         }
     }
+
+    #[test]
+    pub fn test_builder() {
+        // cargo test test_builder -- --nocapture
+        let b = MyHashBuilder::with_seed(55);
+        assert_eq!(b.hash_one("cat"), hash(55, "cat"));
+        assert_eq!(b.build_hasher().finish(), MyHash::with_seed(55).finish());
+        assert!(MyHashBuilder::with_seed(56).hash_one("cat") != hash(55, "cat"));
+    }
+
+    #[test]
+    pub fn test_legacy_unchanged() {
+        // cargo test test_legacy_unchanged -- --nocapture
+        // values from before the rewrite, these must never change
+        assert_eq!(hash_legacy(55, "cat"), 3446027941139861976);
+        assert_eq!(hash_legacy(0, 12345u64), 15882675503472173519);
+        assert_eq!(
+            hash_legacy(7, "a longer key than eight"),
+            3711126103618509577
+        );
+        assert_eq!(hash_legacy(1, (3u8, "x")), 5975987554246679);
+    }
+
+    #[test]
+    pub fn test_lengths() {
+        // cargo test test_lengths -- --nocapture
+        // trailing zeros, and every split around a word boundary
+        let h = |b: &[u8]| {
+            let mut h = MyHash::with_seed(3);
+            h.write(b);
+            h.finish()
+        };
+        let mut seen: Vec<u64> = (0..40).map(|n| h(&vec![0u8; n])).collect();
+        seen.extend((0..40).map(|n| h(&vec![1u8; n])));
+        seen.sort_unstable();
+        seen.dedup();
+        assert_eq!(seen.len(), 79, "the two empty inputs match, nothing else");
+    }
+
+    #[test]
+    pub fn test_avalanche() {
+        // cargo test test_avalanche -- --nocapture
+        // flipping any one input bit should flip each output bit half the time
+        let samples = 2_000;
+        let mut flips = [[0u32; 64]; 64];
+        let mut r = 0x1234_5678u64;
+        for _ in 0..samples {
+            r = fmix(r + 1);
+            let base = hash(9, r);
+            for (i, row) in flips.iter_mut().enumerate() {
+                let diff = base ^ hash(9, r ^ (1 << i));
+                for (o, f) in row.iter_mut().enumerate() {
+                    *f += (diff >> o & 1) as u32;
+                }
+            }
+        }
+        let (lo, hi) = flips
+            .iter()
+            .flatten()
+            .fold((1.0f64, 0.0f64), |(lo, hi), &f| {
+                let p = f as f64 / samples as f64;
+                (lo.min(p), hi.max(p))
+            });
+        println!("output bits flip between {:.3} and {:.3}", lo, hi);
+        assert!(lo > 0.4 && hi < 0.6);
+    }
+
+    #[test]
+    pub fn test_spread() {
+        // cargo test test_spread -- --nocapture
+        // sequential keys over 64 buckets, as HMap uses the low bits
+        let mut counts = [0usize; 64];
+        for x in 0..64_000u32 {
+            counts[(hash(1, x) % 64) as usize] += 1;
+        }
+        let (lo, hi) = (counts.iter().min(), counts.iter().max());
+        println!("bucket sizes {:?} to {:?}", lo, hi);
+        assert!(*lo.unwrap() > 800 && *hi.unwrap() < 1200);
+    }
 }
@@ -0,0 +1,400 @@
+use crate::{BucketList, HMap};
+use std::iter::{Chain, Flatten};
+use std::{slice, vec};
+
+// Every entry is in exactly one of main or grow, even part way
+// through a move, so walking main then grow sees each one once
+
+type Both<I> = Chain<Flatten<I>, Flatten<I>>;
+
+fn both<B: IntoIterator>(main: B, grow: B) -> Both<B::IntoIter>
+where
+    B::Item: IntoIterator,
+{
+    main.into_iter().flatten().chain(grow.into_iter().flatten())
+}
+
+pub struct Iter<'a, K, V> {
+    inner: Both<slice::Iter<'a, Vec<(K, V)>>>,
+    left: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (k, v) = self.inner.next()?;
+        self.left -= 1;
+        Some((k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.left, Some(self.left))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+pub struct IterMut<'a, K, V> {
+    inner: Both<slice::IterMut<'a, Vec<(K, V)>>>,
+    left: usize,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    // the key stays shared, changing it would put it in the wrong bucket
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (k, v) = self.inner.next()?;
+        self.left -= 1;
+        Some((&*k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.left, Some(self.left))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+
+pub struct IntoIter<K, V> {
+    inner: Both<vec::IntoIter<Vec<(K, V)>>>,
+    left: usize,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let kv = self.inner.next()?;
+        self.left -= 1;
+        Some(kv)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.left, Some(self.left))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+pub struct Keys<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+
+pub struct Values<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+
+pub struct ValuesMut<'a, K, V> {
+    inner: IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesMut<'_, K, V> {}
+
+// Takes the entries out but leaves the buckets, so the map
+// keeps its size. Each entry comes off its list's len as it goes,
+// so a Drain that is forgotten leaves the rest still in the map.
+// Anything not read is dropped with the Drain
+pub struct Drain<'a, K, V> {
+    // main then grow
+    lists: [&'a mut BucketList<K, V>; 2],
+    list: usize,
+    bucket: usize,
+}
+
+impl<K, V> Iterator for Drain<'_, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.list < 2 {
+            let l = &mut *self.lists[self.list];
+            while let Some(b) = l.buckets.get_mut(self.bucket) {
+                if let Some(kv) = b.pop() {
+                    l.len -= 1;
+                    return Some(kv);
+                }
+                self.bucket += 1;
+            }
+            self.list += 1;
+            self.bucket = 0;
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.lists[0].len + self.lists[1].len;
+        (left, Some(left))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Drain<'_, K, V> {}
+
+impl<K, V> Drop for Drain<'_, K, V> {
+    fn drop(&mut self) {
+        for _ in self.by_ref() {}
+    }
+}
+
+impl<K, V> BucketList<K, V> {
+    fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, f: &mut F) {
+        for b in &mut self.buckets {
+            b.retain_mut(|(k, v)| f(k, v));
+        }
+        self.len = self.buckets.iter().map(|b| b.len()).sum();
+    }
+}
+
+impl<K, V, S> HMap<K, V, S> {
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: both(&self.main.buckets, &self.grow.buckets),
+            left: self.len(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        let left = self.len();
+        IterMut {
+            inner: both(&mut self.main.buckets, &mut self.grow.buckets),
+            left,
+        }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    // keeps only the entries f says yes to
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        self.main.retain(&mut f);
+        self.grow.retain(&mut f);
+    }
+
+    // empties the map, handing back the entries.
+    // a move in progress carries on over the empty buckets
+    pub fn drain(&mut self) -> Drain<'_, K, V> {
+        Drain {
+            lists: [&mut self.main, &mut self.grow],
+            list: 0,
+            bucket: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.drain();
+    }
+}
+
+impl<K, V, S> IntoIterator for HMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        let left = self.len();
+        IntoIter {
+            inner: both(self.main.buckets, self.grow.buckets),
+            left,
+        }
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a HMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut HMap<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a map part way through moving to more buckets, and how many keys it took.
+    // the seed is random, so that is different every time
+    fn moving() -> (HMap<u32, u32>, u32) {
+        let mut hm = HMap::new();
+        let mut n = 0;
+        while hm.n_moved == 0 || hm.main.len == 0 || hm.grow.len == 0 {
+            hm.insert(n, n * 10);
+            n += 1;
+        }
+        (hm, n)
+    }
+
+    #[test]
+    fn test_iter_each_once() {
+        // cargo test test_iter_each_once -- --nocapture
+        let mut hm = HMap::new();
+        for x in 0..500 {
+            hm.insert(x, x * 10);
+        }
+        for hm in [hm, moving().0] {
+            let mut seen: Vec<(u32, u32)> = hm.iter().map(|(k, v)| (*k, *v)).collect();
+            assert_eq!(hm.iter().len(), hm.len());
+            seen.sort();
+            let want: Vec<(u32, u32)> = (0..hm.len() as u32).map(|x| (x, x * 10)).collect();
+            assert_eq!(seen, want);
+
+            let mut keys: Vec<u32> = hm.keys().copied().collect();
+            keys.sort();
+            assert_eq!(keys, (0..hm.len() as u32).collect::<Vec<_>>());
+            assert_eq!(hm.values().sum::<u32>(), want.iter().map(|p| p.1).sum());
+
+            let mut owned: Vec<(u32, u32)> = hm.into_iter().collect();
+            owned.sort();
+            assert_eq!(owned, want);
+        }
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        // cargo test test_iter_mut -- --nocapture
+        let (mut hm, _) = moving();
+        for (k, v) in hm.iter_mut() {
+            *v += k;
+        }
+        for v in hm.values_mut() {
+            *v += 1;
+        }
+        for (k, v) in &mut hm {
+            *v *= 2;
+            assert_eq!(*v, (k * 11 + 1) * 2);
+        }
+        for (k, v) in &hm {
+            assert_eq!(hm.get(k), Some(v));
+        }
+    }
+
+    #[test]
+    fn test_retain() {
+        // cargo test test_retain -- --nocapture
+        let n: u32 = 500;
+        let mut hm = HMap::new();
+        for x in 0..n {
+            hm.insert(x, x);
+        }
+        hm.retain(|k, v| {
+            *v += 1;
+            k % 3 == 0
+        });
+        assert_eq!(hm.len(), n.div_ceil(3) as usize);
+        for x in 0..n {
+            let want = if x % 3 == 0 { Some(x + 1) } else { None };
+            assert_eq!(hm.get(&x).copied(), want);
+        }
+        let count = |b: &BucketList<u32, u32>| b.buckets.iter().map(|x| x.len()).sum::<usize>();
+        assert_eq!(hm.main.len, count(&hm.main));
+        assert_eq!(hm.grow.len, count(&hm.grow));
+    }
+
+    #[test]
+    fn test_drain_and_clear() {
+        // cargo test test_drain_and_clear -- --nocapture
+        let (mut hm, n) = moving();
+        let mut d = hm.drain();
+        assert_eq!(d.len(), n as usize);
+        let mut got: Vec<(u32, u32)> = d.by_ref().collect();
+        drop(d);
+        got.sort();
+        assert_eq!(got, (0..n).map(|x| (x, x * 10)).collect::<Vec<_>>());
+        assert!(hm.is_empty());
+        assert_eq!(hm.iter().count(), 0);
+
+        // still works after, a move in progress carries on
+        for x in 0..n * 4 {
+            hm.insert(x, x);
+        }
+        assert_eq!(hm.len(), (n * 4) as usize);
+        assert_eq!(hm.get(&7), Some(&7));
+
+        // a drain dropped part way still empties the map
+        let mut d = hm.drain();
+        d.next();
+        drop(d);
+        assert!(hm.is_empty());
+        assert_eq!(hm.iter().count(), 0);
+
+        // forgetting a drain leaves what it didn't take
+        let (mut hm, n) = moving();
+        let mut d = hm.drain();
+        d.next();
+        std::mem::forget(d);
+        assert_eq!(hm.len(), n as usize - 1);
+        assert_eq!(hm.iter().count(), n as usize - 1);
+        let count = |b: &BucketList<u32, u32>| b.buckets.iter().map(|x| x.len()).sum::<usize>();
+        assert_eq!(hm.main.len, count(&hm.main));
+        assert_eq!(hm.grow.len, count(&hm.grow));
+        std::mem::forget(hm.drain());
+        assert_eq!(hm.len(), n as usize - 1);
+        assert_eq!(hm.keys().filter(|k| hm.get(*k).is_some()).count(), hm.len());
+
+        let (mut hm, _) = moving();
+        hm.clear();
+        assert!(hm.is_empty());
+        assert_eq!(hm.get(&1), None);
+        hm.insert(1, 2);
+        assert_eq!(hm.into_iter().collect::<Vec<_>>(), vec![(1, 2)]);
+    }
+}
@@ -1,64 +1,85 @@
+mod entry;
 mod hasher;
+mod iter;
 
-pub use hasher::hash;
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use hasher::{hash, hash_legacy, MyHash, MyHashBuilder, MyHashLegacy};
+pub use iter::{Drain, IntoIter, Iter, IterMut, Keys, Values, ValuesMut};
 use std::borrow::Borrow;
-use std::hash::Hash;
+use std::hash::{BuildHasher, Hash};
 
-const BUCKET_SIZE: usize = 8;
+// entries a bucket of main holds on average before a move starts,
+// so how full the map is says when to grow, not the unluckiest bucket
+const MAX_LOAD: usize = 1;
 // const BUCKET_GROW: usize = 8;
 
+// Buckets of entries, the HMap hashes the keys so
+// every h here is the key's full hash from the map's hasher
 #[derive(Debug)]
 pub struct BucketList<K, V> {
-    seed: u64,
     len: usize,
     buckets: Vec<Vec<(K, V)>>,
 }
 
 impl<K: Hash + Eq, V> BucketList<K, V> {
-    fn new() -> Self {
-        BucketList {
-            seed: rand::random(),
+    fn new(n: usize) -> Self {
+        let mut b = BucketList {
             len: 0,
-            buckets: vec![Vec::new()],
-        }
+            buckets: Vec::new(),
+        };
+        b.set_buckets(n.max(1));
+        b
     }
 
-    // usize returned how big chosen bucket is
-    // tell caller if its too full
-    fn push(&mut self, k: K, v: V) -> usize {
-        let h = (hash(self.seed, &k) as usize) % self.buckets.len();
-        self.buckets[h].push((k, v));
+    // returns the bucket and the position in it
+    fn push(&mut self, h: u64, k: K, v: V) -> (usize, usize) {
+        let b = (h as usize) % self.buckets.len();
+        self.buckets[b].push((k, v));
         self.len += 1;
-        self.buckets[h].len()
+        (b, self.buckets[b].len() - 1)
+    }
+
+    // the bucket and position of k
+    fn find<KB>(&self, h: u64, k: &KB) -> Option<(usize, usize)>
+    where
+        K: Borrow<KB>,
+        KB: Eq + ?Sized,
+    {
+        let b = (h as usize) % self.buckets.len();
+        let pos = self.buckets[b]
+            .iter()
+            .position(|(ik, _)| k == ik.borrow())?;
+        Some((b, pos))
     }
 
     // Key Borrow KB
-    fn get<KB>(&self, k: &KB) -> Option<&V>
+    fn get<KB>(&self, h: u64, k: &KB) -> Option<&V>
     where
         K: Borrow<KB>,
-        KB: Hash + Eq + ?Sized,
+        KB: Eq + ?Sized,
     {
-        let h = (hash(self.seed, &k) as usize) % self.buckets.len();
-        for (ik, iv) in &self.buckets[h] {
-            if k == ik.borrow() {
-                return Some(iv);
-            }
-        }
-        None
+        let (b, pos) = self.find(h, k)?;
+        Some(&self.buckets[b][pos].1)
     }
 
-    fn get_mut<KB>(&mut self, k: &KB) -> Option<&mut V>
+    fn get_mut<KB>(&mut self, h: u64, k: &KB) -> Option<&mut V>
     where
         K: Borrow<KB>,
-        KB: Hash + Eq + ?Sized,
+        KB: Eq + ?Sized,
     {
-        let h = (hash(self.seed, &k) as usize) % self.buckets.len();
-        for (ik, iv) in &mut self.buckets[h] {
-            if k == (ik as &K).borrow() {
-                return Some(iv);
-            }
-        }
-        None
+        let (b, pos) = self.find(h, k)?;
+        Some(&mut self.buckets[b][pos].1)
+    }
+
+    fn remove<KB>(&mut self, h: u64, k: &KB) -> Option<(K, V)>
+    where
+        K: Borrow<KB>,
+        KB: Eq + ?Sized,
+    {
+        let (b, pos) = self.find(h, k)?;
+        self.len -= 1;
+        // order in a bucket doesn't matter
+        Some(self.buckets[b].swap_remove(pos))
     }
 
     fn bucket(&mut self, n: usize) -> Option<Vec<(K, V)>> {
@@ -78,42 +99,76 @@ impl<K: Hash + Eq, V> BucketList<K, V> {
     }
 }
 
+// S makes the hashers, MyHash with a random seed unless
+// with_hasher gives it something else, std's RandomState say.
+// main and grow share it, so a key is only ever hashed once
 #[derive(Debug)]
-pub struct HMap<K, V> {
+pub struct HMap<K, V, S = MyHashBuilder> {
     n_moved: usize,
     main: BucketList<K, V>,
     grow: BucketList<K, V>,
+    hash_builder: S,
+}
+
+impl<K, V, S> HMap<K, V, S> {
+    pub fn len(&self) -> usize {
+        self.main.len + self.grow.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> Default for HMap<K, V, S> {
+    fn default() -> Self {
+        HMap::with_hasher(S::default())
+    }
 }
 
-impl<K: Hash + Eq, V> HMap<K, V> {
+impl<K: Hash + Eq, V> HMap<K, V, MyHashBuilder> {
     pub fn new() -> Self {
+        HMap::with_hasher(MyHashBuilder::new())
+    }
+
+    pub fn with_capacity(n: usize) -> Self {
+        HMap::with_capacity_and_hasher(n, MyHashBuilder::new())
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HMap<K, V, S> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        HMap::with_capacity_and_hasher(0, hash_builder)
+    }
+
+    // A move starts once main holds more than MAX_LOAD a bucket,
+    // so n keys go in without one
+    pub fn with_capacity_and_hasher(n: usize, hash_builder: S) -> Self {
         HMap {
             n_moved: 0,
-            main: BucketList::new(),
-            grow: BucketList::new(),
+            main: BucketList::new(n.div_ceil(MAX_LOAD)),
+            grow: BucketList::new(1),
+            hash_builder,
         }
     }
 
-    pub fn insert(&mut self, k: K, v: V) {
-        if let Some(iv) = self.main.get_mut(&k) {
-            *iv = v;
-            return;
-        }
-        if let Some(iv) = self.grow.get_mut(&k) {
-            *iv = v;
-            return;
-        }
-
-        if self.n_moved > 0 {
-            // we have started move to bigger bucket
-            self.grow.push(k, v);
-            self.move_bucket();
-            return;
-        }
+    fn hash<KR: Hash + ?Sized>(&self, kr: &KR) -> u64 {
+        self.hash_builder.hash_one(kr)
+    }
 
-        if self.main.push(k, v) > BUCKET_SIZE / 2 {
-            // grow buckets
-            self.move_bucket();
+    // a new key goes in through VacantEntry::insert, which keeps the move going
+    pub fn insert(&mut self, k: K, v: V) {
+        match self.entry(k) {
+            Entry::Occupied(mut e) => {
+                e.insert(v);
+            }
+            Entry::Vacant(e) => {
+                e.insert(v);
+            }
         }
     }
 
@@ -122,7 +177,8 @@ impl<K: Hash + Eq, V> HMap<K, V> {
         K: Borrow<KR>,
         KR: Hash + Eq + ?Sized,
     {
-        self.main.get(kr).or_else(|| self.grow.get(kr))
+        let h = self.hash(kr);
+        self.main.get(h, kr).or_else(|| self.grow.get(h, kr))
     }
 
     pub fn get_mut<KR>(&mut self, kr: &KR) -> Option<&mut V>
@@ -130,14 +186,29 @@ impl<K: Hash + Eq, V> HMap<K, V> {
         K: Borrow<KR>,
         KR: Hash + Eq + ?Sized,
     {
-        if let Some(b) = self.main.get_mut(kr) {
+        let h = self.hash(kr);
+        if let Some(b) = self.main.get_mut(h, kr) {
             return Some(b);
         }
-        self.grow.get_mut(kr)
+        self.grow.get_mut(h, kr)
     }
 
-    pub fn len(&self) -> usize {
-        self.main.len + self.grow.len
+    // while a move is in progress the key could be in main or grow
+    pub fn remove<KR>(&mut self, kr: &KR) -> Option<V>
+    where
+        K: Borrow<KR>,
+        KR: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(kr).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry<KR>(&mut self, kr: &KR) -> Option<(K, V)>
+    where
+        K: Borrow<KR>,
+        KR: Hash + Eq + ?Sized,
+    {
+        let h = self.hash(kr);
+        self.main.remove(h, kr).or_else(|| self.grow.remove(h, kr))
     }
 
     pub fn move_bucket(&mut self) {
@@ -146,7 +217,8 @@ impl<K: Hash + Eq, V> HMap<K, V> {
         }
         if let Some(b) = self.main.bucket(self.n_moved) {
             for (k, v) in b {
-                self.grow.push(k, v);
+                let h = self.hash(&k);
+                self.grow.push(h, k, v);
             }
             self.n_moved += 1;
             return;
@@ -203,17 +275,172 @@ mod tests {
         assert_eq!(hm.get(&500), Some(&750));
 
         for (n, x) in hm.main.buckets.iter().enumerate() {
-            assert!(
-                x.len() < 10,
-                format!("main buckets too big {}:{}", n, x.len())
-            )
+            assert!(x.len() < 10, "main buckets too big {}:{}", n, x.len())
         }
 
         for (n, x) in hm.grow.buckets.iter().enumerate() {
-            assert!(
-                x.len() < 10,
-                format!("grow buckets too big {}:{}", n, x.len())
-            )
+            assert!(x.len() < 10, "grow buckets too big {}:{}", n, x.len())
+        }
+    }
+
+    // the len each BucketList keeps has to match what is in its buckets
+    fn check_lens<K, V, S>(hm: &HMap<K, V, S>) {
+        let count = |b: &BucketList<K, V>| b.buckets.iter().map(|x| x.len()).sum::<usize>();
+        assert_eq!(hm.main.len, count(&hm.main));
+        assert_eq!(hm.grow.len, count(&hm.grow));
+    }
+
+    #[test]
+    fn test_remove() {
+        // cargo test test_remove -- --nocapture
+        let mut hm = HMap::new();
+        hm.insert("james".to_string(), 18);
+        hm.insert("dave".to_string(), 45);
+        assert_eq!(hm.remove("dave"), Some(45));
+        assert_eq!(hm.remove("dave"), None);
+        assert_eq!(hm.get("dave"), None);
+        assert_eq!(hm.remove_entry("james"), Some(("james".to_string(), 18)));
+        assert!(hm.is_empty());
+        check_lens(&hm);
+    }
+
+    #[test]
+    fn test_remove_while_moving() {
+        // cargo test test_remove_while_moving -- --nocapture
+        let mut hm = HMap::new();
+        let mut x = 0;
+        // stop part way through a move, so keys are in both lists
+        while hm.n_moved == 0 || hm.main.len == 0 || hm.grow.len == 0 {
+            hm.insert(x, x * 2);
+            x += 1;
+        }
+        println!(
+            "moved {} of {}, {} keys",
+            hm.n_moved,
+            hm.main.buckets.len(),
+            x
+        );
+        for k in 0..x {
+            let in_main = hm.main.get(hm.hash(&k), &k).is_some();
+            assert_eq!(hm.remove(&k), Some(k * 2), "{} in main {}", k, in_main);
+            assert_eq!(hm.get(&k), None);
+            assert_eq!(hm.len(), (x - k - 1) as usize);
+            check_lens(&hm);
+        }
+        assert!(hm.is_empty());
+    }
+
+    #[test]
+    fn test_remove_against_std() {
+        // cargo test test_remove_against_std -- --nocapture
+        use rand::{Rng, SeedableRng};
+        use std::collections::HashMap;
+        let mut r = rand::rngs::StdRng::seed_from_u64(21);
+        let mut hm = HMap::new();
+        let mut model = HashMap::new();
+        for i in 0..50_000 {
+            // a small key space so removes often hit
+            let k: u32 = r.gen_range(0, 2_000);
+            match r.gen_range(0, 3) {
+                0 => {
+                    hm.insert(k, i);
+                    model.insert(k, i);
+                }
+                1 => assert_eq!(hm.remove(&k), model.remove(&k), "remove {}", k),
+                _ => assert_eq!(hm.get(&k), model.get(&k), "get {}", k),
+            }
+            assert_eq!(hm.len(), model.len());
+        }
+        check_lens(&hm);
+        for (k, v) in &model {
+            assert_eq!(hm.get(k), Some(v));
+        }
+    }
+
+    // FNV-1a, about the simplest hasher there is
+    struct Fnv(u64);
+
+    impl Default for Fnv {
+        fn default() -> Self {
+            Fnv(0xcbf29ce484222325)
+        }
+    }
+
+    impl std::hash::Hasher for Fnv {
+        fn write(&mut self, dt: &[u8]) {
+            for d in dt {
+                self.0 = (self.0 ^ *d as u64).wrapping_mul(0x100000001b3);
+            }
+        }
+
+        fn finish(&self) -> u64 {
+            self.0
+        }
+    }
+
+    // the same inserts, gets and removes through any hasher
+    fn exercise<S: BuildHasher>(mut hm: HMap<u32, u32, S>) {
+        for x in 0..5_000 {
+            hm.insert(x, x * 3);
+        }
+        assert_eq!(hm.len(), 5_000);
+        for x in (0..5_000).step_by(2) {
+            assert_eq!(hm.remove(&x), Some(x * 3));
+        }
+        for x in 0..5_000 {
+            let want = if x % 2 == 0 { None } else { Some(x * 3) };
+            assert_eq!(hm.get(&x).copied(), want, "{}", x);
+        }
+        for b in hm.main.buckets.iter().chain(hm.grow.buckets.iter()) {
+            assert!(b.len() < 10, "bucket too big {}", b.len());
+        }
+        check_lens(&hm);
+    }
+
+    #[test]
+    fn test_other_hashers() {
+        // cargo test test_other_hashers -- --nocapture
+        use std::collections::hash_map::RandomState;
+        use std::hash::BuildHasherDefault;
+        exercise(HMap::with_hasher(RandomState::new()));
+        exercise(HMap::<_, _, BuildHasherDefault<Fnv>>::default());
+        exercise(HMap::with_hasher(MyHashBuilder::with_seed(9)));
+
+        let mut hm: HMap<&str, i32, BuildHasherDefault<Fnv>> = HMap::default();
+        *hm.entry("a").or_default() += 2;
+        assert_eq!(hm.get("a"), Some(&2));
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        // cargo test test_with_capacity -- --nocapture
+        for n in [1, 1_000, 10_000] {
+            // any seed, how many go in without a move doesn't depend on it
+            let mut hm = HMap::with_capacity(n);
+            let buckets = hm.main.buckets.len();
+            for x in 0..n {
+                hm.insert(x, x);
+            }
+            // all n went in without a move, so main never changed
+            assert_eq!(hm.n_moved, 0, "{}", n);
+            assert_eq!(hm.main.buckets.len(), buckets);
+            assert_eq!(hm.main.len, n);
+            // one more starts it
+            hm.insert(n, n);
+            assert_eq!(hm.n_moved, 1, "{}", n);
+        }
+        // the same seed gives the same map every time
+        let mut hm = HMap::with_capacity_and_hasher(1_000, MyHashBuilder::with_seed(3));
+        let mut other = HMap::with_capacity_and_hasher(1_000, *hm.hasher());
+        for x in 0..1_000 {
+            hm.insert(x, x);
+            other.insert(x, x);
         }
+        assert_eq!(hm.main.buckets, other.main.buckets);
+        assert_eq!(HMap::<u8, u8>::with_capacity(0).main.buckets.len(), 1);
+        exercise(HMap::with_capacity_and_hasher(
+            10,
+            MyHashBuilder::with_seed(1),
+        ));
     }
 }
@@ -58,8 +58,9 @@ impl Blob {
         (16 + self.k.len() + self.v.len()) as u64
     }
 
+    // the legacy hash, store files place blobs by it
     pub fn k_hash(&self, seed: u64) -> u64 {
-        my_hash_map::hash(seed, &self.k)
+        my_hash_map::hash_legacy(seed, &self.k)
     }
 
     pub fn key_match(&self, rhs: &Self) -> bool {